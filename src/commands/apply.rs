@@ -0,0 +1,54 @@
+use crate::commands::bump;
+use crate::common::logging::{self, Color};
+use crate::common::output::{self, OutputFormat};
+use crate::common::plan::BumpPlan;
+use crate::common::workspace;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// What applying a plan did, printed with `--output json`.
+#[derive(Serialize)]
+struct ApplyOutput<'a> {
+    plan: &'a BumpPlan,
+    dry_run: bool,
+    /// Diffs of the files each branch would change, by branch, when dry-running
+    diffs: BTreeMap<String, String>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    plan: &BumpPlan,
+    dry_run: bool,
+    worktree: bool,
+    tag: bool,
+    tag_template: Option<&str>,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let msg = format!(
+        "Applying plan for bumps {} ({} package versions)",
+        plan.instructions.join(", "),
+        plan.bumps.len()
+    );
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    let diffs = bump::apply_plan(
+        stable_workspace,
+        prerelease_workspace,
+        plan,
+        dry_run,
+        worktree,
+        tag,
+        tag_template,
+        output,
+    )?;
+
+    if output == OutputFormat::Json {
+        output::print_json(&ApplyOutput {
+            plan,
+            dry_run,
+            diffs,
+        });
+    }
+    Ok(())
+}
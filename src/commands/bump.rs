@@ -1,67 +1,488 @@
-use crate::common::bump_tree::instruction::BumpInstruction;
+use crate::common::bump_tree::instruction::{BumpInstruction, ParsedInstruction};
 use crate::common::bump_tree::tree::{BumpTree, ReleaseChannel};
+use crate::common::changeset::Changeset;
+use crate::common::git::{
+    checkout_local_branch, create_annotated_tag, get_current_branch_name, tag_exists,
+};
 use crate::common::logging::{self, Color};
+use crate::common::output::{self, OutputFormat};
+use crate::common::plan::{BumpPlan, PlannedBump, PlannedTag};
+use crate::common::transaction::Transaction;
+use crate::common::version_extension::BumpType;
 use crate::common::workspace::{self};
+use semver::Version;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 
+/// What a bump did, printed with `--output json`.
+#[derive(Serialize)]
+struct BumpOutput<'a> {
+    release_channel: ReleaseChannel,
+    /// Instructions which bumped a package, and so are roots of the bump tree
+    instructions: Vec<RootInstruction>,
+    /// Instructions which were unnecessary, along with why
+    skipped: Vec<SkippedInstruction>,
+    highest_stable: BTreeMap<&'a str, &'a PlannedBump>,
+    highest_prerelease: BTreeMap<&'a str, &'a PlannedBump>,
+    dry_run: bool,
+    /// Diffs of the files each branch would change, by branch, when dry-running
+    diffs: BTreeMap<String, String>,
+    plan_out: Option<&'a Path>,
+    /// Changesets the instructions were read from
+    changesets: &'a [PathBuf],
+}
+
+#[derive(Serialize)]
+struct RootInstruction {
+    instruction: String,
+    package: String,
+    branch: String,
+    from: Version,
+    to: Version,
+    bump_type: BumpType,
+}
+
+#[derive(Serialize)]
+struct SkippedInstruction {
+    instruction: String,
+    reason: String,
+}
+
+/// Bumps to make, along with descriptions of the instructions which were applied and skipped
+type ParsedInstructions = (
+    Vec<BumpInstruction>,
+    Vec<RootInstruction>,
+    Vec<SkippedInstruction>,
+);
+
+#[allow(clippy::too_many_arguments)]
 pub fn exec_stable(
     stable_workspace: &mut workspace::Workspace,
     prerelease_workspace: &mut workspace::Workspace,
     raw_bump_instructions: Vec<&str>,
+    changesets: &[Changeset],
+    notes: &[&str],
     dry_run: bool,
+    worktree: bool,
+    tag: bool,
+    tag_template: Option<&str>,
+    plan_out: Option<&Path>,
+    output: OutputFormat,
 ) -> Result<(), String> {
-    log::info!("⏳Building bump tree...");
-    let bump_instructions = raw_bump_instructions
+    exec(
+        stable_workspace,
+        prerelease_workspace,
+        &raw_bump_instructions,
+        changesets,
+        notes,
+        ReleaseChannel::Stable,
+        dry_run,
+        worktree,
+        tag,
+        tag_template,
+        plan_out,
+        output,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn exec_prerelease(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    raw_bump_instructions: Vec<&str>,
+    changesets: &[Changeset],
+    notes: &[&str],
+    dry_run: bool,
+    worktree: bool,
+    tag: bool,
+    tag_template: Option<&str>,
+    plan_out: Option<&Path>,
+    output: OutputFormat,
+) -> Result<(), String> {
+    exec(
+        stable_workspace,
+        prerelease_workspace,
+        &raw_bump_instructions,
+        changesets,
+        notes,
+        ReleaseChannel::Prerelease,
+        dry_run,
+        worktree,
+        tag,
+        tag_template,
+        plan_out,
+        output,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn exec(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    raw_bump_instructions: &[&str],
+    changesets: &[Changeset],
+    notes: &[&str],
+    release_channel: ReleaseChannel,
+    dry_run: bool,
+    worktree: bool,
+    tag: bool,
+    tag_template: Option<&str>,
+    plan_out: Option<&Path>,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let (mut plan, instructions, skipped) = build_plan(
+        stable_workspace,
+        prerelease_workspace,
+        raw_bump_instructions,
+        release_channel,
+        output,
+    )?;
+    let applied = instructions
         .iter()
-        .filter_map(|s| {
-            match BumpInstruction::from_str(
-                stable_workspace,
-                prerelease_workspace,
-                s,
-                ReleaseChannel::Stable,
-            ) {
-                Ok(Some(i)) => Some(Ok(i)),
-                Ok(None) => {
-                    log::info!("Unnecesarry to apply bump {}, skipping", s);
-                    None
-                }
-                Err(e) => Some(Err(e)),
-            }
-        })
-        .collect::<Result<Vec<_>, String>>()?;
+        .map(|i| i.package.as_str())
+        .collect::<Vec<_>>();
+    plan.record_changesets(changesets, &applied);
+    plan.record_notes(notes)?;
+
+    let diffs = match plan_out {
+        Some(plan_out) => {
+            plan.write(plan_out)?;
+            BTreeMap::new()
+        }
+        None => apply_plan(
+            stable_workspace,
+            prerelease_workspace,
+            &plan,
+            dry_run,
+            worktree,
+            tag,
+            tag_template,
+            output,
+        )?,
+    };
 
+    if output == OutputFormat::Json {
+        output::print_json(&BumpOutput {
+            release_channel,
+            instructions,
+            skipped,
+            highest_stable: highest_bumps(&plan, ReleaseChannel::Stable),
+            highest_prerelease: highest_bumps(&plan, ReleaseChannel::Prerelease),
+            dry_run,
+            diffs,
+            plan_out,
+            changesets: &plan.changesets,
+        });
+    }
+    Ok(())
+}
+
+/// Prints why a package is bumped by the bump instructions, without applying them.
+pub fn exec_explain(
+    stable_workspace: &workspace::Workspace,
+    prerelease_workspace: &workspace::Workspace,
+    raw_bump_instructions: Vec<&str>,
+    release_channel: ReleaseChannel,
+    package: &str,
+) -> Result<(), String> {
+    let (bump_instructions, _, _) = parse_instructions(
+        stable_workspace,
+        prerelease_workspace,
+        &raw_bump_instructions,
+        release_channel,
+    )?;
     let bump_tree = BumpTree::new(
         stable_workspace,
         prerelease_workspace,
         bump_instructions,
-        ReleaseChannel::Stable,
+        release_channel,
+    );
+    print!("{}", bump_tree.explain(package)?);
+    Ok(())
+}
+
+/// Builds the bump tree for the bump instructions, printing it in the output format, and returns
+/// the bumps it makes along with the instructions which were applied and skipped.
+fn build_plan(
+    stable_workspace: &workspace::Workspace,
+    prerelease_workspace: &workspace::Workspace,
+    raw_bump_instructions: &[&str],
+    release_channel: ReleaseChannel,
+    output: OutputFormat,
+) -> Result<(BumpPlan, Vec<RootInstruction>, Vec<SkippedInstruction>), String> {
+    let (bump_instructions, root_instructions, skipped_instructions) = parse_instructions(
+        stable_workspace,
+        prerelease_workspace,
+        raw_bump_instructions,
+        release_channel,
+    )?;
+
+    let bump_tree = BumpTree::new(
+        stable_workspace,
+        prerelease_workspace,
+        bump_instructions,
+        release_channel,
+    );
+
+    match output {
+        OutputFormat::Text if !bump_tree.root_nodes.is_empty() => println!("{}", bump_tree),
+        OutputFormat::Dot => print!("{}", bump_tree.to_dot()),
+        OutputFormat::Mermaid => print!("{}", bump_tree.to_mermaid()),
+        OutputFormat::ReleaseNotes => print!("{}", bump_tree.to_release_notes()),
+        _ => {}
+    }
+
+    let plan = BumpPlan::from_tree(
+        &bump_tree,
+        stable_workspace,
+        prerelease_workspace,
+        raw_bump_instructions,
+        release_channel,
     );
+    Ok((plan, root_instructions, skipped_instructions))
+}
 
-    println!("{}", bump_tree);
+/// Parses the bump instructions, logging those which are unnecessary.
+fn parse_instructions(
+    stable_workspace: &workspace::Workspace,
+    prerelease_workspace: &workspace::Workspace,
+    raw_bump_instructions: &[&str],
+    release_channel: ReleaseChannel,
+) -> Result<ParsedInstructions, String> {
+    log::info!("⏳Building bump tree...");
+    let mut bump_instructions = vec![];
+    let mut root_instructions = vec![];
+    let mut skipped_instructions = vec![];
+    for s in raw_bump_instructions {
+        match BumpInstruction::parse(stable_workspace, prerelease_workspace, s, release_channel)? {
+            ParsedInstruction::Bump(i) => {
+                let branch = match release_channel {
+                    ReleaseChannel::Stable => &stable_workspace.branch_name,
+                    ReleaseChannel::Prerelease => &prerelease_workspace.branch_name,
+                };
+                root_instructions.push(RootInstruction {
+                    instruction: s.to_string(),
+                    package: i.package.borrow().name(),
+                    branch: branch.clone(),
+                    from: i.package.borrow().version(),
+                    to: i.next_version.clone(),
+                    bump_type: i.bump_type(),
+                });
+                bump_instructions.push(i);
+            }
+            ParsedInstruction::Unnecessary(reason) => {
+                log::info!("🤙 {}", reason);
+                log::info!("Unnecesarry to apply bump {}, skipping", s);
+                skipped_instructions.push(SkippedInstruction {
+                    instruction: s.to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+    Ok((bump_instructions, root_instructions, skipped_instructions))
+}
+
+/// The planned bumps on a release channel, by package.
+fn highest_bumps(plan: &BumpPlan, channel: ReleaseChannel) -> BTreeMap<&str, &PlannedBump> {
+    plan.bumps
+        .iter()
+        .filter(|b| b.channel == channel)
+        .map(|b| (b.package.as_str(), b))
+        .collect()
+}
+
+/// Stages the planned bumps, after checking the workspaces are still at the versions they were
+/// planned from, and commits them to the branches of the plan's release channel.
+///
+/// When tagging, an annotated tag is created for each stable bump, after checking up front that
+/// none of them exist yet. Tags are named by `tag_template` if given, or else by the stable
+/// workspace's template, whichever channel is bumped.
+///
+/// When dry-running, nothing is committed and the diff each branch would get is returned by
+/// branch instead.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_plan(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    plan: &BumpPlan,
+    dry_run: bool,
+    worktree: bool,
+    tag: bool,
+    tag_template: Option<&str>,
+    output: OutputFormat,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut diffs = BTreeMap::new();
+    if plan.bumps.is_empty() {
+        logging::bordered_message("No bumps to apply, exiting early.", Color::Green);
+        return Ok(diffs);
+    }
+
+    if let Some(t) = tag_template {
+        stable_workspace.tag_template = t.to_owned();
+    }
+    let tags = match tag {
+        true => plan.tags(&stable_workspace.tag_template),
+        false => vec![],
+    };
+    if tag && tags.is_empty() {
+        log::warn!("⚠️ Tags are only created for stable bumps, and there are none");
+    }
+    check_tags(stable_workspace, &tags)?;
+
+    // Stage the new versions and changelogs, which are only written once the right branch is checked out
+    plan.stage(stable_workspace, prerelease_workspace)?;
 
     if dry_run {
+        if plan.release_channel == ReleaseChannel::Stable {
+            let diff = print_staged_changes(stable_workspace, output)?;
+            diffs.insert(stable_workspace.branch_name.clone(), diff);
+        }
+        let diff = print_staged_changes(prerelease_workspace, output)?;
+        diffs.insert(prerelease_workspace.branch_name.clone(), diff);
+        for tag in tags.iter() {
+            log::info!("🏷️ Would create tag {}", tag.name);
+        }
         log::info!("Dry-run: aborting");
-        return Ok(());
+        return Ok(diffs);
     };
 
+    match plan.release_channel {
+        ReleaseChannel::Stable => apply_stable_plan(
+            stable_workspace,
+            prerelease_workspace,
+            &plan.instructions,
+            &tags,
+            worktree,
+        )?,
+        ReleaseChannel::Prerelease => {
+            apply_prerelease_plan(prerelease_workspace, &plan.instructions, worktree)?
+        }
+    }
+    Ok(diffs)
+}
+
+fn apply_stable_plan(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    raw_bump_instructions: &[String],
+    tags: &[PlannedTag],
+    worktree: bool,
+) -> Result<(), String> {
+    if worktree {
+        let mut transaction = Transaction::begin(&stable_workspace.path, None)?;
+        apply_stable_in_worktrees(
+            stable_workspace,
+            prerelease_workspace,
+            raw_bump_instructions,
+            tags,
+            &mut transaction,
+        )
+        .map_err(|e| transaction.abort(e))?;
+        logging::bordered_message(
+            "Done! Uncommitted changes in your checkout were left untouched",
+            Color::Green,
+        );
+        return Ok(());
+    }
+
+    let original_branch = get_current_branch_name(&stable_workspace.open_repository())?;
+    let mut transaction = Transaction::begin(&stable_workspace.path, Some(&original_branch))?;
+    apply_stable(
+        stable_workspace,
+        prerelease_workspace,
+        raw_bump_instructions,
+        tags,
+        &mut transaction,
+    )
+    .map_err(|e| transaction.abort(e))?;
+
+    // Check back out to the original branch before exiting.
+    let msg = format!(
+        "Done! Checking back out to branch '{}' before exiting",
+        original_branch
+    );
+    logging::bordered_message(msg.as_str(), Color::Green);
+    checkout_local_branch(&stable_workspace.open_repository(), &original_branch)
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn apply_prerelease_plan(
+    prerelease_workspace: &mut workspace::Workspace,
+    raw_bump_instructions: &[String],
+    worktree: bool,
+) -> Result<(), String> {
+    let msg = format!(
+        "Applying prerelease version bumps to branch '{}'",
+        prerelease_workspace.branch_name
+    );
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    let message = format!("Apply bumps {}", raw_bump_instructions.join(", "));
+
+    if worktree {
+        let mut transaction = Transaction::begin(&prerelease_workspace.path, None)?;
+        let branch_name = prerelease_workspace.branch_name.clone();
+        transaction
+            .track_branch(&branch_name)
+            .and_then(|_| prerelease_workspace.commit_in_worktree(&branch_name, &message))
+            .map_err(|e| transaction.abort(e))?;
+        return Ok(());
+    }
+
+    let original_branch = get_current_branch_name(&prerelease_workspace.open_repository())?;
+    let mut transaction = Transaction::begin(&prerelease_workspace.path, Some(&original_branch))?;
+    transaction
+        .track_branch(&prerelease_workspace.branch_name)
+        .and_then(|_| prerelease_workspace.checkout_local_branch())
+        .and_then(|_| {
+            prerelease_workspace.write_manifests();
+            prerelease_workspace.update_lockfile()
+        })
+        .and_then(|_| prerelease_workspace.stage_and_commit_all(&message))
+        .map_err(|e| transaction.abort(e))?;
+
+    // Check back out to the original branch before exiting.
+    let msg = format!(
+        "Done! Checking back out to branch '{}' before exiting",
+        original_branch
+    );
+    logging::bordered_message(msg.as_str(), Color::Green);
+    checkout_local_branch(&prerelease_workspace.open_repository(), &original_branch)
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Writes and commits the staged stable bumps, then propagates them to a new branch off the
+/// prerelease branch.
+fn apply_stable(
+    stable_workspace: &workspace::Workspace,
+    prerelease_workspace: &workspace::Workspace,
+    raw_bump_instructions: &[String],
+    tags: &[PlannedTag],
+    transaction: &mut Transaction,
+) -> Result<(), String> {
     let msg = format!(
         "Applying stable version bumps to branch '{}'",
         stable_workspace.branch_name
     );
     logging::bordered_message(msg.as_str(), Color::Blue);
     stable_workspace.checkout_local_branch()?;
-    for (_, n) in bump_tree.highest_stable.iter() {
-        let i = n.stable.as_ref().expect("must exist here");
-        i.package.borrow_mut().set_version(&i.next_version);
-    }
+    transaction.track_branch(&stable_workspace.branch_name)?;
+    stable_workspace.write_manifests();
 
     stable_workspace.update_lockfile()?;
 
     stable_workspace.stage_and_commit_all(
         format!("Apply bumps {}", raw_bump_instructions.join(", ")).as_str(),
     )?;
+    create_tags(stable_workspace, tags, transaction)?;
 
     // TODO Actually make prerelease workspace optional
-    if let Some(prerelease_workspace) = Some(&prerelease_workspace) {
+    if let Some(prerelease_workspace) = Some(prerelease_workspace) {
         let msg = format!(
             "Applying prerelease version bumps to branch '{}'",
             prerelease_workspace.branch_name
@@ -69,24 +490,13 @@ pub fn exec_stable(
         logging::bordered_message(msg.as_str(), Color::Blue);
         prerelease_workspace.checkout_local_branch()?;
 
-        let prerelease_branch_name = format!(
-            "propagate-{}-bump-to-prerelease-{}",
-            raw_bump_instructions
-                .iter()
-                .map(|s| s.replace(" ", "_"))
-                .collect::<Vec<_>>()
-                .join("-"),
-            chrono::offset::Utc::now().format("%Y-%m-%d")
-        );
+        let prerelease_branch_name = propagation_branch_name(raw_bump_instructions);
+        transaction.track_branch(&prerelease_branch_name)?;
         prerelease_workspace
             .create_and_checkout_branch(prerelease_branch_name.as_str())
             .map_err(|e| e.to_string())?;
 
-        for (_, n) in bump_tree.highest_prerelease.iter() {
-            let i = n.prerelease.as_ref().expect("must exist here");
-            i.package.borrow_mut().set_version(&i.next_version);
-        }
-
+        prerelease_workspace.write_manifests();
         prerelease_workspace.update_lockfile()?;
         prerelease_workspace.stage_and_commit_all(
             format!(
@@ -99,78 +509,206 @@ pub fn exec_stable(
         log::info!("❗❗❗ Don't forget to run `git push {} {}` and open a PR to update the prerelease branch!", stable_workspace.remote_name, prerelease_branch_name);
     }
 
-    // Check back out to the original branch before exiting.
+    Ok(())
+}
+
+/// Like `apply_stable`, but commits in temporary worktrees so uncommitted changes in the checkout
+/// are left untouched.
+fn apply_stable_in_worktrees(
+    stable_workspace: &mut workspace::Workspace,
+    prerelease_workspace: &mut workspace::Workspace,
+    raw_bump_instructions: &[String],
+    tags: &[PlannedTag],
+    transaction: &mut Transaction,
+) -> Result<(), String> {
     let msg = format!(
-        "Done! Checking back out to stable branch '{}' before exiting",
+        "Applying stable version bumps to branch '{}' in a worktree",
         stable_workspace.branch_name
     );
-    logging::bordered_message(msg.as_str(), Color::Green);
-    stable_workspace.checkout_local_branch()?;
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    let stable_branch_name = stable_workspace.branch_name.clone();
+    transaction.track_branch(&stable_branch_name)?;
+    stable_workspace.commit_in_worktree(
+        &stable_branch_name,
+        format!("Apply bumps {}", raw_bump_instructions.join(", ")).as_str(),
+    )?;
+    create_tags(stable_workspace, tags, transaction)?;
+
+    let msg = format!(
+        "Applying prerelease version bumps to a branch off '{}' in a worktree",
+        prerelease_workspace.branch_name
+    );
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    let prerelease_branch_name = propagation_branch_name(raw_bump_instructions);
+    transaction.track_branch(&prerelease_branch_name)?;
+    prerelease_workspace.create_branch(&prerelease_branch_name)?;
+    prerelease_workspace.commit_in_worktree(
+        &prerelease_branch_name,
+        format!(
+            "Propagate stable {} bump to prerelease",
+            raw_bump_instructions.join(", ")
+        )
+        .as_str(),
+    )?;
+
+    log::info!("❗❗❗ Don't forget to run `git push {} {}` and open a PR to update the prerelease branch!", stable_workspace.remote_name, prerelease_branch_name);
 
     Ok(())
 }
 
-pub fn exec_prerelease(
-    stable_workspace: &mut workspace::Workspace,
-    prerelease_workspace: &mut workspace::Workspace,
-    raw_bump_instructions: Vec<&str>,
-    dry_run: bool,
+/// Checks the tags have valid names, and that none of them exist yet, so tags already pushed for
+/// a release are never moved.
+fn check_tags(workspace: &workspace::Workspace, tags: &[PlannedTag]) -> Result<(), String> {
+    let repo = workspace.open_repository();
+    let mut names = BTreeSet::new();
+    for tag in tags {
+        if !git2::Reference::is_valid_name(&format!("refs/tags/{}", tag.name)) {
+            return Err(format!(
+                "Invalid tag name {}, check the tag template {}",
+                tag.name, workspace.tag_template
+            ));
+        }
+        if !names.insert(&tag.name) {
+            return Err(format!(
+                "Tag {} would be created twice, the tag template {} must include {{name}}",
+                tag.name, workspace.tag_template
+            ));
+        }
+        if tag_exists(&repo, &tag.name) {
+            return Err(format!(
+                "Tag {} already exists, refusing to overwrite it",
+                tag.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Creates the tags on the head of the workspace branch.
+fn create_tags(
+    workspace: &workspace::Workspace,
+    tags: &[PlannedTag],
+    transaction: &mut Transaction,
 ) -> Result<(), String> {
-    log::info!("⏳Building bump tree...");
-    let bump_instructions = raw_bump_instructions
-        .iter()
-        .filter_map(|s| {
-            match BumpInstruction::from_str(
-                stable_workspace,
-                prerelease_workspace,
-                s,
-                ReleaseChannel::Prerelease,
-            ) {
-                Ok(Some(i)) => Some(Ok(i)),
-                Ok(None) => {
-                    log::info!("Unnecesarry to apply bump {}, skipping", s);
-                    None
-                }
-                Err(e) => Some(Err(e)),
-            }
-        })
-        .collect::<Result<Vec<_>, String>>()?;
+    let repo = workspace.open_repository();
+    for tag in tags {
+        transaction.track_tag(&tag.name);
+        create_annotated_tag(&repo, &tag.name, &workspace.branch_name, &tag.message)
+            .map_err(|e| format!("Failed to create tag {}: {}", tag.name, e))?;
+    }
+    Ok(())
+}
 
-    let bump_tree = BumpTree::new(
-        stable_workspace,
-        prerelease_workspace,
-        bump_instructions,
-        ReleaseChannel::Prerelease,
-    );
-    prerelease_workspace.checkout_local_branch()?;
+/// Name of the branch stable bumps are propagated to the prerelease branch on.
+fn propagation_branch_name(raw_bump_instructions: &[String]) -> String {
+    format!(
+        "propagate-{}-bump-to-prerelease-{}",
+        raw_bump_instructions
+            .iter()
+            .map(|s| s.replace(" ", "_"))
+            .collect::<Vec<_>>()
+            .join("-"),
+        chrono::offset::Utc::now().format("%Y-%m-%d")
+    )
+}
 
-    if bump_tree.root_nodes.is_empty() {
-        logging::bordered_message("No bumps to apply, exiting early.", Color::Green);
-        return Ok(());
+/// Prints a unified diff of the files the staged version bumps would change on a branch, unless
+/// outputting JSON, and returns it.
+fn print_staged_changes(
+    workspace: &workspace::Workspace,
+    output: OutputFormat,
+) -> Result<String, String> {
+    let msg = format!("Dry-run: changes to branch '{}'", workspace.branch_name);
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    let diff = workspace.diff()?;
+    if diff.is_empty() {
+        log::info!("No files would change");
+    } else if output == OutputFormat::Text {
+        print!("{}", diff);
     }
+    Ok(diff)
+}
 
-    println!("{}", bump_tree);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::mocks::init_mock_repository;
+    use crate::common::workspace::Workspace;
+    use git2::Repository;
+    use tempdir::TempDir;
 
-    if dry_run {
-        log::info!("Dry-run: aborting");
-        return Ok(());
+    /// The inherited mock workspace with a `main` stable branch and a `next` prerelease branch, and
+    /// an empty `origin` remote.
+    fn mock_workspaces() -> (Repository, Workspace, Workspace) {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let remote_path = TempDir::new("workspace-version-tools-remote")
+            .unwrap()
+            .into_path();
+        Repository::init_bare(&remote_path).unwrap();
+        repo.remote("origin", remote_path.to_str().unwrap())
+            .unwrap();
+        {
+            let head = repo.head().unwrap().peel_to_commit().unwrap();
+            repo.branch("next", &head, false).unwrap();
+        }
+
+        let stable_workspace =
+            Workspace::new_from_git(path.clone(), Some("main"), "origin").unwrap();
+        let prerelease_workspace = Workspace::new_from_git(path, Some("next"), "origin").unwrap();
+        (repo, stable_workspace, prerelease_workspace)
     }
 
-    let msg = format!(
-        "Applying prerelease version bumps to branch '{}'",
-        prerelease_workspace.branch_name
-    );
-    logging::bordered_message(msg.as_str(), Color::Blue);
-    for (_, n) in bump_tree.highest_prerelease.iter() {
-        let i = n.prerelease.as_ref().expect("must exist here");
-        i.package.borrow_mut().set_version(&i.next_version);
+    #[test]
+    fn tags_stable_bumps_with_the_tag_template() {
+        let (repo, mut stable_workspace, mut prerelease_workspace) = mock_workspaces();
+
+        exec_stable(
+            &mut stable_workspace,
+            &mut prerelease_workspace,
+            vec!["z patch"],
+            &[],
+            &[],
+            false,
+            true,
+            true,
+            Some("release/{name}/{version}"),
+            None,
+            OutputFormat::Text,
+        )
+        .unwrap();
+
+        assert!(tag_exists(&repo, "release/z/0.1.1"));
+        assert!(!tag_exists(&repo, "z-v0.1.1"));
     }
 
-    prerelease_workspace.update_lockfile()?;
+    #[test]
+    fn bumping_the_prerelease_channel_creates_no_tags() {
+        let (repo, mut stable_workspace, mut prerelease_workspace) = mock_workspaces();
+        let stable_head = repo.refname_to_id("refs/heads/main").unwrap();
+        let prerelease_head = repo.refname_to_id("refs/heads/next").unwrap();
 
-    prerelease_workspace.stage_and_commit_all(
-        format!("Apply bumps {}", raw_bump_instructions.join(", ")).as_str(),
-    )?;
+        exec_prerelease(
+            &mut stable_workspace,
+            &mut prerelease_workspace,
+            vec!["z minor"],
+            &[],
+            &[],
+            false,
+            true,
+            true,
+            Some("release/{name}/{version}"),
+            None,
+            OutputFormat::Text,
+        )
+        .unwrap();
 
-    Ok(())
+        // Only the prerelease branch is bumped, and tags are only created for stable bumps
+        assert_eq!(repo.refname_to_id("refs/heads/main").unwrap(), stable_head);
+        assert_ne!(
+            repo.refname_to_id("refs/heads/next").unwrap(),
+            prerelease_head
+        );
+        assert!(repo.tag_names(None).unwrap().is_empty());
+    }
 }
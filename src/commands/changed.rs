@@ -0,0 +1,74 @@
+use crate::common::logging::{self, Color};
+use crate::common::output::{self, OutputFormat};
+use crate::common::version_extension::BumpType;
+use crate::common::workspace::Workspace;
+use semver::Version;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Packages changed since a ref, printed with `--output json`.
+#[derive(Serialize)]
+struct ChangedOutput {
+    since: String,
+    packages: BTreeMap<String, ChangedPackage>,
+    /// Present when asked to emit bump instructions
+    bump_instructions: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct ChangedPackage {
+    version: Version,
+    /// Changed files, relative to the workspace root
+    files: Vec<PathBuf>,
+}
+
+pub fn exec(
+    workspace: &Workspace,
+    since: &str,
+    bump_type: Option<BumpType>,
+    output: OutputFormat,
+) -> Result<(), String> {
+    log::info!("⏳Finding packages changed since {}...", since);
+    let changed = workspace.changed_packages(since)?;
+    let packages = changed
+        .into_iter()
+        .map(|(name, files)| {
+            let version = workspace.packages[&name].borrow().version();
+            (name, ChangedPackage { version, files })
+        })
+        .collect::<BTreeMap<_, _>>();
+    let bump_instructions = bump_type.map(|bump_type| {
+        packages
+            .keys()
+            .map(|name| format!("{} {}", name, bump_type))
+            .collect::<Vec<_>>()
+    });
+
+    if output == OutputFormat::Json {
+        output::print_json(&ChangedOutput {
+            since: since.to_owned(),
+            packages,
+            bump_instructions,
+        });
+        return Ok(());
+    }
+
+    let msg = format!("{} packages changed since {}", packages.len(), since);
+    logging::bordered_message(msg.as_str(), Color::Blue);
+    for (name, package) in packages.iter() {
+        println!(
+            "📦 {} v{} ({} files changed)",
+            name,
+            package.version,
+            package.files.len()
+        );
+    }
+    if let Some(bump_instructions) = bump_instructions {
+        println!();
+        for bump_instruction in bump_instructions {
+            println!("--bump-instruction \"{}\"", bump_instruction);
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,25 @@
+use crate::common::output::{self, OutputFormat};
 use crate::common::workspace::Workspace;
+use semver::Version;
+use serde::Serialize;
+use std::collections::BTreeMap;
 
-pub async fn exec(workspace: &mut Workspace) {
-    for package in workspace.packages.values_mut() {
+/// Versions changed by the command, printed with `--output json`.
+#[derive(Serialize)]
+struct MakeAtLeastStableOutput {
+    updated: BTreeMap<String, UpdatedVersion>,
+}
+
+#[derive(Serialize)]
+struct UpdatedVersion {
+    from: Version,
+    to: Version,
+}
+
+pub async fn exec(workspace: &mut Workspace, output: OutputFormat) {
+    let mut updated = BTreeMap::new();
+    let mut new_versions = vec![];
+    for package in workspace.packages.values() {
         let cur_version = package.borrow().version();
 
         // Remove any prerelease suffix
@@ -23,7 +41,26 @@ pub async fn exec(workspace: &mut Workspace) {
                 cur_version,
                 new_version
             );
-            package.borrow_mut().set_version(&new_version);
+            updated.insert(
+                package.borrow().name(),
+                UpdatedVersion {
+                    from: cur_version,
+                    to: new_version.clone(),
+                },
+            );
+            new_versions.push((package, new_version));
         }
     }
+
+    workspace.set_package_versions(
+        &new_versions
+            .iter()
+            .map(|(p, v)| (*p, v))
+            .collect::<Vec<_>>(),
+    );
+    workspace.write_manifests();
+
+    if output == OutputFormat::Json {
+        output::print_json(&MakeAtLeastStableOutput { updated });
+    }
 }
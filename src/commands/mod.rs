@@ -1,3 +1,6 @@
+pub mod apply;
 pub mod bump;
+pub mod changed;
 pub mod make_at_least_stable;
+pub mod publish;
 pub mod sync;
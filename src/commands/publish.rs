@@ -0,0 +1,411 @@
+use crate::common::output::{self, OutputFormat};
+use crate::common::registry::{Registries, RegistryIndex, CRATES_IO};
+use crate::common::workspace::Workspace;
+use semver::Version;
+use serde::Serialize;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+/// How to publish the packages.
+pub struct PublishOptions {
+    /// Whether to run `cargo publish --dry-run` instead
+    pub dry_run: bool,
+    /// Times to retry a failed `cargo publish`
+    pub retries: u32,
+    /// Delay before retrying, and between checks of whether a published version is in the index
+    pub delay: Duration,
+    /// How long to wait for a published version to appear in the index before publishing its
+    /// dependents
+    pub wait_timeout: Duration,
+}
+
+/// What publishing did, printed with `--output json`.
+#[derive(Serialize)]
+struct PublishOutput {
+    dry_run: bool,
+    /// In the order they were published in, up to the first failure
+    packages: Vec<PackageOutcome>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct PackageOutcome {
+    name: String,
+    version: Version,
+    /// The registry published to, None when the package isn't published anywhere
+    #[serde(skip_serializing_if = "Option::is_none")]
+    registry: Option<String>,
+    #[serde(flatten)]
+    outcome: Outcome,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+enum Outcome {
+    Published { attempts: u32 },
+    AlreadyPublished,
+    PublishFalse,
+    Failed { error: String },
+}
+
+/// Publishes every package in dependency order to each registry its `publish` field allows,
+/// skipping those with `publish = false` or whose version is already in the registry's index.
+/// Stops at the first package which fails to publish, as its dependents can't be published
+/// without it.
+pub async fn exec(
+    workspace: &Workspace,
+    registries: &mut Registries,
+    options: &PublishOptions,
+    output: OutputFormat,
+) -> Result<(), String> {
+    let outcomes = publish_all(workspace, registries, options, |name, registry| {
+        cargo_publish(workspace, name, registry, options.dry_run)
+    })
+    .await?;
+
+    let failure = match outcomes.last() {
+        Some(PackageOutcome {
+            name,
+            registry,
+            outcome: Outcome::Failed { error },
+            ..
+        }) => Some(format!(
+            "Failed to publish {} to {}: {}",
+            name,
+            registry.as_deref().unwrap_or(CRATES_IO),
+            error
+        )),
+        _ => None,
+    };
+
+    if output == OutputFormat::Json {
+        output::print_json(&PublishOutput {
+            dry_run: options.dry_run,
+            packages: outcomes,
+        });
+    }
+
+    match failure {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+async fn publish_all(
+    workspace: &Workspace,
+    registries: &mut Registries,
+    options: &PublishOptions,
+    mut publish: impl FnMut(&str, &str) -> Result<(), String>,
+) -> Result<Vec<PackageOutcome>, String> {
+    let order = workspace.topological_order()?;
+    let mut outcomes = vec![];
+    for (i, name) in order.iter().enumerate() {
+        let progress = format!("[{}/{}]", i + 1, order.len());
+        let (package_registries, version) = {
+            let package = workspace.packages[name].borrow();
+            (package.publish_registries(), package.version())
+        };
+
+        if package_registries.is_empty() {
+            log::info!("{} 💤 {} publish = false, skipping", progress, name);
+            outcomes.push(PackageOutcome {
+                name: name.clone(),
+                version,
+                registry: None,
+                outcome: Outcome::PublishFalse,
+            });
+            continue;
+        }
+
+        for registry in package_registries {
+            // Dependents can only be published once the registry's index has the version
+            let has_dependents_in_registry = workspace.packages[name]
+                .borrow()
+                .direct_workspace_dependents()
+                .any(|d| d.borrow().publish_registries().contains(&registry));
+            let outcome = match registries.index(&registry) {
+                Ok(index) => {
+                    publish_to(
+                        index,
+                        name,
+                        &version,
+                        &registry,
+                        has_dependents_in_registry,
+                        options,
+                        &progress,
+                        &mut publish,
+                    )
+                    .await
+                }
+                Err(error) => Outcome::Failed { error },
+            };
+
+            let failed = matches!(outcome, Outcome::Failed { .. });
+            if let Outcome::Failed { error } = &outcome {
+                log::error!(
+                    "{} ❌ Failed to publish {} to {}: {}",
+                    progress,
+                    name,
+                    registry,
+                    error
+                );
+            }
+            outcomes.push(PackageOutcome {
+                name: name.clone(),
+                version: version.clone(),
+                registry: Some(registry),
+                outcome,
+            });
+            if failed {
+                return Ok(outcomes);
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+/// Publishes a package to a registry unless its index already has the version.
+#[allow(clippy::too_many_arguments)]
+async fn publish_to(
+    index: &RegistryIndex,
+    name: &str,
+    version: &Version,
+    registry: &str,
+    has_dependents_in_registry: bool,
+    options: &PublishOptions,
+    progress: &str,
+    publish: &mut impl FnMut(&str, &str) -> Result<(), String>,
+) -> Outcome {
+    match index.is_published(name, version).await {
+        Ok(true) => {
+            log::info!(
+                "{} ✅ {} v{} already published to {}",
+                progress,
+                name,
+                version,
+                registry
+            );
+            Outcome::AlreadyPublished
+        }
+        Ok(false) => {
+            log::info!(
+                "{} 📦 Publishing {} v{} to {}",
+                progress,
+                name,
+                version,
+                registry
+            );
+            match publish_with_retries(name, registry, options, publish).await {
+                Ok(attempts) if options.dry_run || !has_dependents_in_registry => {
+                    Outcome::Published { attempts }
+                }
+                Ok(attempts) => match wait_until_published(index, name, version, options).await {
+                    Ok(()) => Outcome::Published { attempts },
+                    Err(error) => Outcome::Failed { error },
+                },
+                Err(error) => Outcome::Failed { error },
+            }
+        }
+        Err(error) => Outcome::Failed { error },
+    }
+}
+
+/// Publishes a package, retrying on failure, and returns the number of attempts it took.
+async fn publish_with_retries(
+    name: &str,
+    registry: &str,
+    options: &PublishOptions,
+    publish: &mut impl FnMut(&str, &str) -> Result<(), String>,
+) -> Result<u32, String> {
+    let attempts = options.retries + 1;
+    for attempt in 1..=attempts {
+        match publish(name, registry) {
+            Ok(()) => return Ok(attempt),
+            Err(e) if attempt == attempts => return Err(e),
+            Err(e) => {
+                log::warn!(
+                    "⚠️ Failed to publish {} to {} (attempt {}/{}), retrying in {:?}: {}",
+                    name,
+                    registry,
+                    attempt,
+                    attempts,
+                    options.delay,
+                    e
+                );
+                tokio::time::sleep(options.delay).await;
+            }
+        }
+    }
+    unreachable!("the last attempt always returns")
+}
+
+/// Waits for a published version to appear in the registry index.
+async fn wait_until_published(
+    index: &RegistryIndex,
+    name: &str,
+    version: &Version,
+    options: &PublishOptions,
+) -> Result<(), String> {
+    let start = Instant::now();
+    while !index.is_published(name, version).await? {
+        if start.elapsed() >= options.wait_timeout {
+            return Err(format!(
+                "v{} didn't appear in the registry index within {:?}",
+                version, options.wait_timeout
+            ));
+        }
+        log::info!(
+            "⏳Waiting for {} v{} to appear in the registry index",
+            name,
+            version
+        );
+        tokio::time::sleep(options.delay).await;
+    }
+    Ok(())
+}
+
+fn cargo_publish(
+    workspace: &Workspace,
+    name: &str,
+    registry: &str,
+    dry_run: bool,
+) -> Result<(), String> {
+    let mut command = Command::new("cargo");
+    command
+        .arg("publish")
+        .arg("--package")
+        .arg(name)
+        .arg("--manifest-path")
+        .arg(workspace.path.join("Cargo.toml"))
+        .current_dir(&workspace.path);
+    if registry != CRATES_IO {
+        command.arg("--registry").arg(registry);
+    }
+    if dry_run {
+        command.arg("--dry-run");
+    }
+    let output = command
+        .output()
+        .map_err(|e| format!("Failed to execute command: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::mocks::copy_mock_workspace;
+    use crate::common::registry::DEFAULT_USER_AGENT;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[tokio::test]
+    async fn publishes_in_dependency_order() {
+        let path = copy_mock_workspace("inherited_workspace");
+        let edit = |file: &str, from: &str, to: &str| {
+            let content = fs::read_to_string(path.join(file)).unwrap();
+            fs::write(path.join(file), content.replace(from, to)).unwrap();
+        };
+        edit(
+            "Cargo.toml",
+            "version = \"1.0.0\"\nedition",
+            "version = \"1.0.0\"\npublish = [\"internal\"]\nedition",
+        );
+        edit(
+            "x/Cargo.toml",
+            "name = \"x\"",
+            "name = \"x\"\npublish.workspace = true",
+        );
+        edit(
+            "y/Cargo.toml",
+            "name = \"y\"",
+            "name = \"y\"\npublish = [\"internal\", \"mirror\"]",
+        );
+        edit(
+            "z/Cargo.toml",
+            "name = \"z\"",
+            "name = \"z\"\npublish = false",
+        );
+        let workspace = Workspace::new_test_workspace(path.clone()).unwrap();
+
+        // Each registry has its own local index, configured in the workspace's cargo config
+        let index_dir = TempDir::new("workspace-version-tools-index").unwrap();
+        let mut config = String::new();
+        for registry in ["internal", "mirror"] {
+            fs::create_dir_all(index_dir.path().join(registry).join("1")).unwrap();
+            config.push_str(&format!(
+                "[registries.{}]\nindex = \"file://{}\"\n",
+                registry,
+                index_dir.path().join(registry).display()
+            ));
+        }
+        fs::create_dir_all(path.join(".cargo")).unwrap();
+        fs::write(path.join(".cargo/config.toml"), config).unwrap();
+        // y is already on the mirror
+        fs::write(
+            index_dir.path().join("mirror/1/y"),
+            "{\"name\":\"y\",\"vers\":\"1.0.0\"}\n",
+        )
+        .unwrap();
+
+        let mut registries = Registries::new(&path, DEFAULT_USER_AGENT, None);
+        let options = PublishOptions {
+            dry_run: false,
+            retries: 2,
+            delay: Duration::from_millis(1),
+            wait_timeout: Duration::from_secs(5),
+        };
+
+        // Publishing y fails once, and x only appears in the index once published
+        let mut published = vec![];
+        let outcomes = publish_all(&workspace, &mut registries, &options, |name, registry| {
+            let attempt = format!("{} to {}", name, registry);
+            if name == "y" && !published.contains(&format!("{} (failed)", attempt)) {
+                published.push(format!("{} (failed)", attempt));
+                return Err("timed out".to_owned());
+            }
+            fs::write(
+                index_dir.path().join(registry).join("1").join(name),
+                format!("{{\"name\":\"{}\",\"vers\":\"1.0.0\"}}\n", name),
+            )
+            .unwrap();
+            published.push(attempt);
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            published,
+            vec!["x to internal", "y to internal (failed)", "y to internal"]
+        );
+        let outcomes = outcomes
+            .into_iter()
+            .map(|o| (o.name, o.registry, o.outcome))
+            .collect::<Vec<_>>();
+        let registry = |r: &str| Some(r.to_owned());
+        assert_eq!(
+            outcomes,
+            vec![
+                (
+                    "x".to_owned(),
+                    registry("internal"),
+                    Outcome::Published { attempts: 1 }
+                ),
+                (
+                    "y".to_owned(),
+                    registry("internal"),
+                    Outcome::Published { attempts: 2 }
+                ),
+                (
+                    "y".to_owned(),
+                    registry("mirror"),
+                    Outcome::AlreadyPublished
+                ),
+                ("z".to_owned(), None, Outcome::PublishFalse),
+            ]
+        );
+    }
+}
@@ -1,75 +1,172 @@
+use crate::common::output::{self, OutputFormat};
 use crate::common::package::Package;
+use crate::common::registry::Registries;
 use crate::common::workspace::Workspace;
-use crates_io_api::AsyncClient;
 use semver::Version;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 
-pub async fn exec(workspace: &mut Workspace) {
-    // Instantiate the client.
-    log::info!("Instantiating crates.io api client");
-    let client = AsyncClient::new(
-        "my-user-agent (liam@parity.io)",
-        std::time::Duration::from_millis(1000),
-    )
-    .expect("Failed to create crates.io api client");
+/// Outcome of syncing each package, printed with `--output json`.
+#[derive(Serialize)]
+struct SyncOutput {
+    packages: BTreeMap<String, Outcome>,
+}
 
+/// Sets every package's version to the latest one published to the first registry its `publish`
+/// field allows, along with the requirements other members declare on it.
+pub async fn exec(workspace: &mut Workspace, registries: &mut Registries, output: OutputFormat) {
     // Check every manifest
+    let mut outcomes = BTreeMap::new();
+    let mut new_versions = vec![];
     let total_files = workspace.packages.len();
-    for (i, package) in workspace.packages.values_mut().enumerate() {
+    for (i, package) in workspace.packages.values().enumerate() {
         let progress = format!("[{}/{}]", i, total_files);
-        match sync_manifest(&client, &mut package.borrow_mut()).await {
-            Ok(outcome) => match outcome {
-                Outcome::AlreadyUpdated(v) => {
-                    log::info!(
-                        "{} ✅ {} already synced: {}",
-                        progress,
-                        package.borrow().name(),
-                        v
-                    );
-                }
-                Outcome::Updated(prev_version, new_version) => {
-                    log::info!(
-                        "{} 📝 Updated {} Cargo.toml to match crates.io ({} -> {})",
-                        progress,
-                        package.borrow().name(),
-                        prev_version,
-                        new_version
-                    );
-                }
-                Outcome::PublishFalse => {
-                    log::info!(
-                        "{} 💤 {} publish = false, skipping",
-                        progress,
-                        package.borrow().name()
-                    )
-                }
-            },
-            Err(e) => log::error!(
+        let outcome = sync_manifest(registries, package)
+            .await
+            .unwrap_or_else(|error| Outcome::Failed { error });
+        match &outcome {
+            Outcome::AlreadyUpdated { registry, version } => {
+                log::info!(
+                    "{} ✅ {} already synced with {}: {}",
+                    progress,
+                    package.borrow().name(),
+                    registry,
+                    version
+                );
+            }
+            Outcome::Updated { registry, from, to } => {
+                log::info!(
+                    "{} 📝 Updated {} Cargo.toml to match {} ({} -> {})",
+                    progress,
+                    package.borrow().name(),
+                    registry,
+                    from,
+                    to
+                );
+                new_versions.push((package, to.clone()));
+            }
+            Outcome::PublishFalse => {
+                log::info!(
+                    "{} 💤 {} publish = false, skipping",
+                    progress,
+                    package.borrow().name()
+                )
+            }
+            Outcome::Failed { error } => log::error!(
                 "{} ❌ Failed to check {} {}",
                 progress,
                 package.borrow().name(),
-                e
+                error
             ),
         }
+        outcomes.insert(package.borrow().name(), outcome);
     }
-}
 
-async fn sync_manifest(client: &AsyncClient, package: &mut Package) -> Result<Outcome, String> {
-    if !package.publish() {
-        return Ok(Outcome::PublishFalse);
+    // Set the versions together, so requirements on them and inherited versions are kept in sync
+    workspace.set_package_versions(
+        &new_versions
+            .iter()
+            .map(|(p, v)| (*p, v))
+            .collect::<Vec<_>>(),
+    );
+    workspace.write_manifests();
+
+    if output == OutputFormat::Json {
+        output::print_json(&SyncOutput { packages: outcomes });
     }
-    let package_version_before = package.version();
-    let crates_version = package.crates_io_version(client).await?;
-    // If versions dont match, update local to match crates.io
-    if package_version_before != crates_version {
-        package.set_version(&crates_version);
-        return Ok(Outcome::Updated(package_version_before, crates_version));
+}
+
+async fn sync_manifest(
+    registries: &mut Registries,
+    package: &RefCell<Package>,
+) -> Result<Outcome, String> {
+    let (name, package_version_before, registry) = {
+        let package = package.borrow();
+        let Some(registry) = package.publish_registries().into_iter().next() else {
+            return Ok(Outcome::PublishFalse);
+        };
+        (package.name(), package.version(), registry)
+    };
+    let published_version = registries
+        .index(&registry)?
+        .latest_version(&name)
+        .await?
+        .ok_or_else(|| format!("no version is published to {}", registry))?;
+    // If versions dont match, update local to match the registry
+    if package_version_before != published_version {
+        return Ok(Outcome::Updated {
+            registry,
+            from: package_version_before,
+            to: published_version,
+        });
     };
 
-    Ok(Outcome::AlreadyUpdated(package_version_before))
+    Ok(Outcome::AlreadyUpdated {
+        registry,
+        version: package_version_before,
+    })
 }
 
+#[derive(Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
 pub enum Outcome {
-    AlreadyUpdated(Version),
-    Updated(Version, Version),
+    AlreadyUpdated {
+        registry: String,
+        version: Version,
+    },
+    Updated {
+        registry: String,
+        from: Version,
+        to: Version,
+    },
     PublishFalse,
+    Failed {
+        error: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::mocks::copy_mock_workspace;
+    use crate::common::registry::DEFAULT_USER_AGENT;
+    use std::fs;
+    use std::path::Path;
+    use tempdir::TempDir;
+
+    fn publish(index: &Path, path: &str, name: &str, version: &str) {
+        fs::create_dir_all(index.join(path).parent().unwrap()).unwrap();
+        fs::write(
+            index.join(path),
+            format!("{{\"name\":\"{}\",\"vers\":\"{}\"}}\n", name, version),
+        )
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn syncs_requirements_and_inherited_versions() {
+        let mut workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        let index = TempDir::new("workspace-version-tools-index").unwrap();
+        publish(index.path(), "1/x", "x", "1.1.0");
+        publish(index.path(), "1/y", "y", "1.1.0");
+        publish(index.path(), "1/z", "z", "0.1.0");
+        let mut registries = Registries::new(
+            &workspace.path,
+            DEFAULT_USER_AGENT,
+            Some(&format!("file://{}", index.path().display())),
+        );
+
+        exec(&mut workspace, &mut registries, OutputFormat::Text).await;
+
+        // x and y both moved to the same version, so keep inheriting it
+        let root = fs::read_to_string(workspace.path.join("Cargo.toml")).unwrap();
+        assert!(root.contains(r#"version = "1.1.0""#));
+        assert!(root.contains(r#"x = { path = "x", version = "1.1.0" }"#));
+        assert!(root.contains(r#"renamed-y = { package = "y", path = "y", version = "1.1.0" }"#));
+        let x = fs::read_to_string(workspace.path.join("x/Cargo.toml")).unwrap();
+        assert!(x.contains("version.workspace = true"));
+        assert!(workspace.packages["x"].borrow().inherits_version());
+    }
 }
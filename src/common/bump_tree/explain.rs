@@ -0,0 +1,167 @@
+use super::node::BumpNode;
+use super::tree::{BumpTree, ReleaseChannel};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Paths beyond this many are left out, as there can be exponentially many in large workspaces
+const MAX_PATHS: usize = 100;
+
+impl BumpTree<'_> {
+    /// Describes why `package` is bumped: every path from a root of the tree to it through
+    /// dependents, with the bumps carried along each edge, marking the paths which decided its
+    /// highest stable and prerelease bumps.
+    pub fn explain(&self, package: &str) -> Result<String, String> {
+        let nodes = self
+            .highest_stable
+            .iter()
+            .chain(self.highest_prerelease.iter())
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let node = nodes.get(package).ok_or_else(|| {
+            format!(
+                "Package {} isn't bumped by these bump instructions",
+                package
+            )
+        })?;
+
+        let stable_path = winning_path(&nodes, package, ReleaseChannel::Stable);
+        let prerelease_path = winning_path(&nodes, package, ReleaseChannel::Prerelease);
+
+        let mut explanation = format!("🔎 Why is {} bumped?\n", package);
+        for (channel, instruction, path) in [
+            ("stable", &node.stable, &stable_path),
+            ("prerelease", &node.prerelease, &prerelease_path),
+        ] {
+            if let Some(i) = instruction {
+                writeln!(
+                    explanation,
+                    "  {} {} -> {} ({}), decided by {}",
+                    channel,
+                    i.package.borrow().version(),
+                    i.next_version,
+                    i.bump_type(),
+                    path.join(" -> ")
+                )
+                .unwrap();
+            }
+        }
+
+        // Search from the roots, and from the starts of the winning paths in case one started at a
+        // requested bump which lost to a dependency's on the other release channel
+        let mut starts = self
+            .root_nodes
+            .iter()
+            .map(|n| n.package_name())
+            .collect::<Vec<_>>();
+        for path in [&stable_path, &prerelease_path] {
+            if let Some(start) = path.first() {
+                if !starts.contains(start) {
+                    starts.push(start.clone());
+                }
+            }
+        }
+        let mut paths = vec![];
+        for start in starts {
+            self.collect_paths(&nodes, package, vec![start], &mut paths);
+        }
+
+        writeln!(explanation, "\nPaths from the requested bumps:").unwrap();
+        for path in paths.iter() {
+            write!(explanation, "  {}", path[0]).unwrap();
+            for edge in path.windows(2) {
+                write!(
+                    explanation,
+                    " ─[{}]→ {}",
+                    self.carried_bumps(&nodes[&edge[0]], &edge[1]),
+                    edge[1]
+                )
+                .unwrap();
+            }
+            if *path == stable_path && node.stable.is_some() {
+                write!(explanation, " 🏆 highest stable").unwrap();
+            }
+            if *path == prerelease_path && node.prerelease.is_some() {
+                write!(explanation, " 🏆 highest prerelease").unwrap();
+            }
+            writeln!(explanation).unwrap();
+        }
+        if paths.len() >= MAX_PATHS {
+            writeln!(
+                explanation,
+                "  (only the first {} paths are shown)",
+                MAX_PATHS
+            )
+            .unwrap();
+        }
+
+        Ok(explanation)
+    }
+
+    /// Extends `path` through the dependents of its last package which are bumped, collecting the
+    /// paths which reach `target`.
+    fn collect_paths(
+        &self,
+        nodes: &BTreeMap<String, Rc<BumpNode>>,
+        target: &str,
+        path: Vec<String>,
+        paths: &mut Vec<Vec<String>>,
+    ) {
+        if paths.len() >= MAX_PATHS {
+            return;
+        }
+        let last = path.last().expect("paths are never empty");
+        if last == target {
+            paths.push(path);
+            return;
+        }
+        let dependents = self
+            .dependents(last)
+            .into_iter()
+            .filter(|d| nodes.contains_key(d) && !path.contains(d))
+            .collect::<BTreeSet<_>>();
+        for dependent in dependents {
+            let mut next = path.clone();
+            next.push(dependent);
+            self.collect_paths(nodes, target, next, paths);
+        }
+    }
+
+    /// The bump types a dependency's highest bumps cause on a dependent, e.g. "stable major,
+    /// prerelease minor".
+    fn carried_bumps(&self, dependency: &BumpNode, dependent: &str) -> String {
+        let (stable, prerelease) = self.carried_bump(dependency, dependent);
+        let carried = [("stable", stable), ("prerelease", prerelease)]
+            .into_iter()
+            .filter_map(|(channel, i)| i.map(|i| format!("{} {}", channel, i.bump_type())))
+            .collect::<Vec<_>>();
+        if carried.is_empty() {
+            "none".to_owned()
+        } else {
+            carried.join(", ")
+        }
+    }
+}
+
+/// The chain of causes of a package's highest bump on a release channel, from the requested bump
+/// down to the package.
+fn winning_path(
+    nodes: &BTreeMap<String, Rc<BumpNode>>,
+    package: &str,
+    channel: ReleaseChannel,
+) -> Vec<String> {
+    let mut path = vec![package.to_owned()];
+    let mut current = package.to_owned();
+    while let Some(cause) = nodes.get(&current).and_then(|n| match channel {
+        ReleaseChannel::Stable => n.stable_cause.clone(),
+        ReleaseChannel::Prerelease => n.prerelease_cause.clone(),
+    }) {
+        if path.contains(&cause) {
+            break;
+        }
+        path.push(cause.clone());
+        current = cause;
+    }
+    path.reverse();
+    path
+}
@@ -0,0 +1,175 @@
+use super::instruction::BumpInstruction;
+use super::node::BumpNode;
+use super::tree::BumpTree;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Colours of major and compatible bumps, matching the terminal rendering of the tree
+const MAJOR_COLOR: &str = "#d62728";
+const COMPATIBLE_COLOR: &str = "#1f77b4";
+
+/// A dependency edge between two bumped packages.
+struct Edge {
+    dependency: String,
+    dependent: String,
+    /// Whether the dependency's bump decided the dependent's highest bump on either release channel
+    significant: bool,
+}
+
+impl BumpTree<'_> {
+    /// Renders the bumped packages and the dependency edges between them as a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        let nodes = self.graph_nodes();
+        let mut dot = String::from("digraph bumps {\n    rankdir=LR;\n    node [shape=box];\n");
+        for (name, node) in nodes.iter() {
+            let mut label = name.clone();
+            for line in self.node_details(name, node) {
+                label.push_str("\\n");
+                label.push_str(&line);
+            }
+            let root = self.is_root(name);
+            writeln!(
+                dot,
+                "    \"{}\" [label=\"{}\", color=\"{}\", fontcolor=\"{}\"{}];",
+                name,
+                label,
+                node_color(node),
+                node_color(node),
+                if root { ", penwidth=2" } else { "" },
+            )
+            .unwrap();
+        }
+        for edge in self.graph_edges(&nodes) {
+            let style = if edge.significant {
+                "style=bold"
+            } else {
+                "style=dashed, color=\"#999999\""
+            };
+            writeln!(
+                dot,
+                "    \"{}\" -> \"{}\" [{}];",
+                edge.dependency, edge.dependent, style
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the bumped packages and the dependency edges between them as a Mermaid flowchart.
+    pub fn to_mermaid(&self) -> String {
+        let nodes = self.graph_nodes();
+        let mut mermaid = String::from("flowchart LR\n");
+        for (name, node) in nodes.iter() {
+            let mut label = name.clone();
+            for line in self.node_details(name, node) {
+                label.push_str("<br/>");
+                label.push_str(&line);
+            }
+            writeln!(mermaid, "    {}[\"{}\"]", mermaid_id(name), label).unwrap();
+        }
+        for edge in self.graph_edges(&nodes) {
+            let arrow = if edge.significant { "==>" } else { "-.->" };
+            writeln!(
+                mermaid,
+                "    {} {} {}",
+                mermaid_id(&edge.dependency),
+                arrow,
+                mermaid_id(&edge.dependent)
+            )
+            .unwrap();
+        }
+        writeln!(
+            mermaid,
+            "    classDef major stroke:{},color:{}",
+            MAJOR_COLOR, MAJOR_COLOR
+        )
+        .unwrap();
+        writeln!(
+            mermaid,
+            "    classDef compatible stroke:{},color:{}",
+            COMPATIBLE_COLOR, COMPATIBLE_COLOR
+        )
+        .unwrap();
+        writeln!(mermaid, "    classDef root stroke-width:3px").unwrap();
+        for (name, node) in nodes.iter() {
+            let class = if node.is_breaking() {
+                "major"
+            } else {
+                "compatible"
+            };
+            writeln!(mermaid, "    class {} {}", mermaid_id(name), class).unwrap();
+            if self.is_root(name) {
+                writeln!(mermaid, "    class {} root", mermaid_id(name)).unwrap();
+            }
+        }
+        mermaid
+    }
+
+    /// Every bumped package, sorted by name so the graphs are deterministic.
+    pub(super) fn graph_nodes(&self) -> BTreeMap<String, Rc<BumpNode>> {
+        self.highest_stable
+            .iter()
+            .chain(self.highest_prerelease.iter())
+            .map(|(name, node)| (name.clone(), node.clone()))
+            .collect()
+    }
+
+    /// Edges from each bumped package to its bumped dependents, sorted by dependency then
+    /// dependent.
+    fn graph_edges(&self, nodes: &BTreeMap<String, Rc<BumpNode>>) -> Vec<Edge> {
+        let mut edges = vec![];
+        for (dependent, node) in nodes.iter() {
+            for dependency in self.dependencies(dependent) {
+                if nodes.contains_key(&dependency) {
+                    edges.push(Edge {
+                        // The dependency may decide the bump on either release channel
+                        significant: node.stable_cause.as_ref() == Some(&dependency)
+                            || node.prerelease_cause.as_ref() == Some(&dependency),
+                        dependency,
+                        dependent: dependent.clone(),
+                    });
+                }
+            }
+        }
+        edges.sort_by(|a, b| (&a.dependency, &a.dependent).cmp(&(&b.dependency, &b.dependent)));
+        edges
+    }
+
+    /// Lines describing a node's bumps and version group.
+    fn node_details(&self, name: &str, node: &BumpNode) -> Vec<String> {
+        let mut details = vec![];
+        if let Some(i) = &node.stable {
+            details.push(format!("stable {}", version_change(i)));
+        }
+        if let Some(i) = &node.prerelease {
+            details.push(format!("prerelease {}", version_change(i)));
+        }
+        if let Some(group) = self.version_group(name) {
+            details.push(format!("group {}", group));
+        }
+        details
+    }
+
+    fn is_root(&self, name: &str) -> bool {
+        self.root_nodes.iter().any(|n| n.package_name() == name)
+    }
+}
+
+fn version_change(i: &BumpInstruction) -> String {
+    format!("{} -> {}", i.package.borrow().version(), i.next_version)
+}
+
+fn node_color(node: &BumpNode) -> &'static str {
+    if node.is_breaking() {
+        MAJOR_COLOR
+    } else {
+        COMPATIBLE_COLOR
+    }
+}
+
+/// Package names as Mermaid node ids, whose dashes could otherwise be read as part of an edge.
+fn mermaid_id(name: &str) -> String {
+    name.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+}
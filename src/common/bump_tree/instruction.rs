@@ -2,7 +2,7 @@ use super::tree::{BumpTree, ReleaseChannel};
 use crate::common::version_extension::{EndUserInitiated, VersionExtension};
 use crate::common::workspace::Workspace;
 use crate::common::{package::Package, version_extension::BumpType};
-use semver::Version;
+use semver::{Prerelease, Version};
 use std::{
     cell::RefCell,
     collections::HashSet,
@@ -16,7 +16,17 @@ pub struct BumpInstruction {
     pub next_version: Version,
 }
 
+/// A bump instruction parsed from its string form.
+#[derive(Debug, Clone)]
+pub enum ParsedInstruction {
+    Bump(BumpInstruction),
+    /// The bump has already been made, so there's nothing to do. Holds the reason why.
+    Unnecessary(String),
+}
+
 impl BumpInstruction {
+    /// The kind of change from the current version. Releasing a prerelease, e.g. `1.0.0-rc.1` ->
+    /// `1.0.0`, is compatible with it, so counts as a patch bump.
     pub fn bump_type(&self) -> BumpType {
         let cur_version = self.package.borrow().version();
         if self.next_version.major > cur_version.major
@@ -27,23 +37,116 @@ impl BumpInstruction {
             BumpType::Major
         } else if self.next_version.minor > cur_version.minor {
             BumpType::Minor
-        } else {
+        } else if self.next_version.patch > cur_version.patch
+            || (self.next_version.pre.is_empty() && !cur_version.pre.is_empty())
+        {
             BumpType::Patch
+        } else {
+            BumpType::Prerelease
+        }
+    }
+
+    /// A new iteration of the package's current prerelease, e.g. `1.3.0-alpha.4` ->
+    /// `1.3.0-alpha.5`.
+    fn prerelease_iteration(workspace: &Workspace, name: &str) -> Result<BumpInstruction, String> {
+        let package = workspace.packages.get(name).ok_or_else(|| {
+            format!(
+                "Package {} not found on branch {}",
+                name, workspace.branch_name
+            )
+        })?;
+        let cur_version = package.borrow().version();
+        let next_version = cur_version.bump_prerelease_iteration().ok_or_else(|| {
+            format!(
+                "Package {} (v{}) on branch {} isn't a prerelease, so has no prerelease iteration to bump",
+                name, cur_version, workspace.branch_name
+            )
+        })?;
+
+        Ok(BumpInstruction {
+            package: package.clone(),
+            next_version,
+        })
+    }
+
+    /// Bump straight to `next_version`, which must not be lower than the current version.
+    fn explicit_version(
+        workspace: &Workspace,
+        name: &str,
+        next_version: Version,
+    ) -> Result<ParsedInstruction, String> {
+        let package = workspace.packages.get(name).ok_or_else(|| {
+            format!(
+                "Package {} not found on branch {}",
+                name, workspace.branch_name
+            )
+        })?;
+        let cur_version = package.borrow().version();
+        if next_version < cur_version {
+            return Err(format!(
+                "Package {} (v{}) on branch {} can't be downgraded to v{}",
+                name, cur_version, workspace.branch_name, next_version
+            ));
         }
+        if next_version == cur_version {
+            return Ok(ParsedInstruction::Unnecessary(format!(
+                "Package {} is already v{} on branch {}, so there is no need to bump it",
+                name, cur_version, workspace.branch_name
+            )));
+        }
+
+        Ok(ParsedInstruction::Bump(BumpInstruction {
+            package: package.clone(),
+            next_version,
+        }))
     }
 
+    /// Shorthand for `parse` for tests which only care whether a bump is needed.
+    #[cfg(test)]
     pub fn from_str(
         stable_workspace: &Workspace,
         prerelease_workspace: &Workspace,
         s: &str,
         release_channel: ReleaseChannel,
     ) -> Result<Option<BumpInstruction>, String> {
+        match Self::parse(stable_workspace, prerelease_workspace, s, release_channel)? {
+            ParsedInstruction::Bump(i) => Ok(Some(i)),
+            ParsedInstruction::Unnecessary(_) => Ok(None),
+        }
+    }
+
+    /// Parses a bump instruction such as "pallet-balances minor" or "pallet-balances =2.0.0" into
+    /// the bump to make on the release channel, or the reason no bump is needed.
+    pub fn parse(
+        stable_workspace: &Workspace,
+        prerelease_workspace: &Workspace,
+        s: &str,
+        release_channel: ReleaseChannel,
+    ) -> Result<ParsedInstruction, String> {
         let parts: Vec<&str> = s.splitn(2, ' ').collect();
         let name = parts[0].to_string();
-        let semver_part = parts
+        let bump_part = parts
             .get(1)
-            .map(|b| BumpType::from_str(b))
-            .unwrap_or_else(|| Err(format!("Invalid Bump Instruction: '{}'", s).to_string()))?;
+            .ok_or_else(|| format!("Invalid Bump Instruction: '{}'", s))?;
+        let channel_workspace = match release_channel {
+            ReleaseChannel::Stable => stable_workspace,
+            ReleaseChannel::Prerelease => prerelease_workspace,
+        };
+
+        // Explicit target versions only depend on the current version of the package.
+        if let Some(version) = bump_part.strip_prefix('=') {
+            let next_version = Version::parse(version.trim())
+                .map_err(|e| format!("Invalid version in Bump Instruction '{}': {}", s, e))?;
+            return Self::explicit_version(channel_workspace, &name, next_version);
+        }
+
+        let semver_part = BumpType::from_str(bump_part)?;
+
+        // Prerelease iterations only depend on the current version of the package.
+        if semver_part == BumpType::Prerelease {
+            return Self::prerelease_iteration(channel_workspace, &name)
+                .map(ParsedInstruction::Bump);
+        }
 
         let stable_package = match (stable_workspace.packages.get(&name), &release_channel) {
             // If we have a package, we can proceed
@@ -57,18 +160,17 @@ impl BumpInstruction {
             }
             (None, ReleaseChannel::Prerelease) => {
                 // If there's no stable package for a prerelease bump, there's no need to do anything.
-                log::info!(
-                    "🤙 Prerelease package {} isn't yet on the stable release channel, so there is no need to bump it",
-                    name,
-                );
-                return Ok(None);
+                return Ok(ParsedInstruction::Unnecessary(format!(
+                    "Prerelease package {} isn't yet on the stable release channel, so there is no need to bump it",
+                    name
+                )));
             }
         };
         let cur_stable_version = stable_package.borrow().version();
 
         match (release_channel, prerelease_workspace.packages.get(&name)) {
             // Stable is easy, just bump the version.
-            (ReleaseChannel::Stable, _) => Ok(Some(BumpInstruction {
+            (ReleaseChannel::Stable, _) => Ok(ParsedInstruction::Bump(BumpInstruction {
                 package: stable_package.clone(),
                 next_version: cur_stable_version.bump(semver_part, EndUserInitiated::Yes),
             })),
@@ -81,25 +183,27 @@ impl BumpInstruction {
             // existing stable package.
             (ReleaseChannel::Prerelease, Some(prerelease_package)) => {
                 let cur_prerelease_version = prerelease_package.borrow().version();
+                let prerelease_identifier = cur_prerelease_version
+                    .prerelease_identifier()
+                    .unwrap_or_else(|| prerelease_workspace.prerelease_identifier.clone());
                 match semver_part {
                     BumpType::Major => {
                         // Ignore minor bump if already ahead on major
                         if cur_prerelease_version.major > cur_stable_version.major {
-                            log::info!(
-                                "🤙 Prerelease package {} (v{}) is already a major bump ahead of stable (v{}), so there is no need to major bump it",
+                            return Ok(ParsedInstruction::Unnecessary(format!(
+                                "Prerelease package {} (v{}) is already a major bump ahead of stable (v{}), so there is no need to major bump it",
                                 name,
                                 cur_prerelease_version,
                                 cur_stable_version
-                           );
-                            return Ok(None);
+                            )));
                         }
 
                         // Need to bump to stable major+1
-                        Ok(Some(BumpInstruction {
+                        Ok(ParsedInstruction::Bump(BumpInstruction {
                             package: prerelease_package.clone(),
                             next_version: cur_stable_version
                                 .bump(BumpType::Major, EndUserInitiated::Yes)
-                                .with_prerelease(),
+                                .with_prerelease(&prerelease_identifier),
                         }))
                     }
                     BumpType::Minor => {
@@ -107,21 +211,20 @@ impl BumpInstruction {
                         if cur_prerelease_version.major > cur_stable_version.major
                             || cur_prerelease_version.minor > cur_stable_version.minor
                         {
-                            log::info!(
-                                "🤙 Prerelease package {} (v{}) is already a minor bump ahead of stable (v{}), so there is no need to minor bump it",
+                            return Ok(ParsedInstruction::Unnecessary(format!(
+                                "Prerelease package {} (v{}) is already a minor bump ahead of stable (v{}), so there is no need to minor bump it",
                                 name,
                                 cur_prerelease_version,
                                 cur_stable_version
-                           );
-                            return Ok(None);
+                            )));
                         }
 
                         // Need to bump to stable minor+1
-                        Ok(Some(BumpInstruction {
+                        Ok(ParsedInstruction::Bump(BumpInstruction {
                             package: prerelease_package.clone(),
                             next_version: cur_stable_version
                                 .bump(BumpType::Minor, EndUserInitiated::Yes)
-                                .with_prerelease(),
+                                .with_prerelease(&prerelease_identifier),
                         }))
                     }
                     BumpType::Patch => {
@@ -130,23 +233,23 @@ impl BumpInstruction {
                             || cur_prerelease_version.minor > cur_stable_version.minor
                             || cur_prerelease_version.patch > cur_stable_version.patch
                         {
-                            log::info!(
-                                "🤙 Prerelease package {} (v{}) is already a patch bump ahead of stable (v{}), so there is no need to patch bump it",
+                            return Ok(ParsedInstruction::Unnecessary(format!(
+                                "Prerelease package {} (v{}) is already a patch bump ahead of stable (v{}), so there is no need to patch bump it",
                                 name,
                                 cur_prerelease_version,
                                 cur_stable_version
-                           );
-                            return Ok(None);
+                            )));
                         }
 
                         // Need to bump to stable patch+1
-                        Ok(Some(BumpInstruction {
+                        Ok(ParsedInstruction::Bump(BumpInstruction {
                             package: prerelease_package.clone(),
                             next_version: cur_stable_version
                                 .bump(BumpType::Patch, EndUserInitiated::Yes)
-                                .with_prerelease(),
+                                .with_prerelease(&prerelease_identifier),
                         }))
                     }
+                    BumpType::Prerelease => unreachable!("prerelease iterations handled above"),
                 }
             }
         }
@@ -164,71 +267,75 @@ impl PartialEq for BumpInstruction {
 /// Prerelease bump type is influenced by the parent and the next stable bump type.
 /// It also requires a stable package to exist for this child, otherwise the prerelease
 /// isn't being bumped in relation to anything.
+///
+/// The prerelease identifier of the package is kept if it already has one, otherwise
+/// `prerelease_identifier` is used.
 pub fn compute_prerelease_bump_instruction(
     prerelease_package: Option<&Rc<RefCell<Package>>>,
     stable_package: Option<&Rc<RefCell<Package>>>,
     stable_bump_instruction: Option<&BumpInstruction>,
     prerelease_parent_bump_instruction: Option<&BumpInstruction>,
+    prerelease_identifier: &Prerelease,
 ) -> Option<BumpInstruction> {
     // If there's no prerelease package, there's nothing to bump
-    let prerelease_package = match prerelease_package {
-        Some(p) => p,
-        None => return None,
-    };
+    let prerelease_package = prerelease_package?;
     let cur_prerelease_version = prerelease_package.borrow().version();
+    let prerelease_identifier = cur_prerelease_version
+        .prerelease_identifier()
+        .unwrap_or_else(|| prerelease_identifier.clone());
+
+    // A dependency republished with a new prerelease iteration requires the prerelease dependent
+    // to be republished with a new iteration too, whether or not it's on stable yet.
+    if let Some(parent) = prerelease_parent_bump_instruction {
+        if parent.bump_type() == BumpType::Prerelease && stable_bump_instruction.is_none() {
+            if let Some(next_version) = cur_prerelease_version.bump_prerelease_iteration() {
+                return Some(BumpInstruction {
+                    package: prerelease_package.clone(),
+                    next_version,
+                });
+            }
+        }
+    }
 
     // If there's no stable package, then there's no reason to bump the prerelease version because
     // its current version is already ready to release to stable.
-    let stable_package = match stable_package {
-        Some(p) => p,
-        None => return None,
-    };
+    let stable_package = stable_package?;
     let cur_stable_version = stable_package.borrow().version();
 
     // First candidate for the bump type is based on the bump type required of the prerelease
     // package to remain semver compliant relative to the new stable version.
-    let candidate1 = stable_bump_instruction
-        .map(|i| {
-            match i.bump_type() {
-                // Prerelease API is broken relative to stable. Need to major bump prerelease relative to
-                // stable.
-                BumpType::Major | BumpType::Minor => Some(
-                    i.next_version
-                        .bump(BumpType::Major, EndUserInitiated::No)
-                        .with_prerelease(),
-                ),
-                // Stable API is not breaking relative to stable, so we can just bump the prerelease by
-                // a patch to keep pace with the change in stable. But only if prerelease is not
-                // already ahead of stable by minor or major or patch.
-                BumpType::Patch => Some(
-                    i.next_version
-                        .bump(BumpType::Patch, EndUserInitiated::No)
-                        .with_prerelease(),
-                ),
-            }
-        })
-        .flatten();
+    let candidate1 = stable_bump_instruction.map(|i| {
+        match i.bump_type() {
+            // Prerelease API is broken relative to stable. Need to major bump prerelease relative to
+            // stable.
+            BumpType::Major | BumpType::Minor => i
+                .next_version
+                .bump(BumpType::Major, EndUserInitiated::No)
+                .with_prerelease(&prerelease_identifier),
+            // Stable API is not breaking relative to stable, so we can just bump the prerelease by
+            // a patch to keep pace with the change in stable. But only if prerelease is not
+            // already ahead of stable by minor or major or patch.
+            BumpType::Patch | BumpType::Prerelease => i
+                .next_version
+                .bump(BumpType::Patch, EndUserInitiated::No)
+                .with_prerelease(&prerelease_identifier),
+        }
+    });
 
     // Second candidate for the bump type is based on the bump type of the prerelease parent
-    let candidate2 = prerelease_parent_bump_instruction
-        .map(|i| {
-            match i.bump_type() {
-                // Parent breaking change. Bump if not already bumped to be the stable version + major.
-                BumpType::Major => Some(
-                    cur_stable_version
-                        .bump(BumpType::Major, EndUserInitiated::No)
-                        .with_prerelease(),
-                ),
-                // Parent compatible change. Bump if not already bumped to be the stable major
-                // minor or patch
-                BumpType::Minor | BumpType::Patch => Some(
-                    cur_stable_version
-                        .bump(BumpType::Patch, EndUserInitiated::No)
-                        .with_prerelease(),
-                ),
-            }
-        })
-        .flatten();
+    let candidate2 = prerelease_parent_bump_instruction.map(|i| {
+        match i.bump_type() {
+            // Parent breaking change. Bump if not already bumped to be the stable version + major.
+            BumpType::Major => cur_stable_version
+                .bump(BumpType::Major, EndUserInitiated::No)
+                .with_prerelease(&prerelease_identifier),
+            // Parent compatible change. Bump if not already bumped to be the stable major
+            // minor or patch
+            BumpType::Minor | BumpType::Patch | BumpType::Prerelease => cur_stable_version
+                .bump(BumpType::Patch, EndUserInitiated::No)
+                .with_prerelease(&prerelease_identifier),
+        }
+    });
 
     let highest_candidate = match (candidate1.clone(), candidate2.clone()) {
         (Some(c1), Some(c2)) => Some(std::cmp::max(c1, c2)),
@@ -237,34 +344,32 @@ pub fn compute_prerelease_bump_instruction(
         (None, None) => None,
     };
 
-    highest_candidate
-        .map(|v| {
-            // Only return if current prerelease is not higher than our highest candidate here.
-            if cur_prerelease_version >= v {
-                None
-            } else {
-                Some(BumpInstruction {
-                    package: prerelease_package.clone(),
-                    next_version: v,
-                })
-            }
-        })
-        .flatten()
+    highest_candidate.and_then(|v| {
+        // Only return if current prerelease is not higher than our highest candidate here.
+        if cur_prerelease_version >= v {
+            None
+        } else {
+            Some(BumpInstruction {
+                package: prerelease_package.clone(),
+                next_version: v,
+            })
+        }
+    })
 }
 
 impl Display for BumpTree<'_> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
+        writeln!(
             f,
-            "🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲\n"
+            "🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲"
         )?;
-        write!(
+        writeln!(
             f,
-            "🌲 Bump Tree (duplicates emitted, breaking bumps prioritised) 🌲\n"
+            "🌲 Bump Tree (highest bump of each package, under its cause) 🌲"
         )?;
-        write!(
+        writeln!(
             f,
-            "🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲\n"
+            "🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲🌲"
         )?;
         for node in self.root_nodes.iter() {
             self.fmt_node(node, f, "".to_string(), true)?;
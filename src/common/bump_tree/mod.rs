@@ -1,5 +1,8 @@
+pub mod explain;
+pub mod graph;
 pub mod instruction;
 pub mod node;
+pub mod release_notes;
 pub mod tree;
 
 #[cfg(test)]
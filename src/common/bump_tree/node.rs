@@ -1,11 +1,17 @@
 use super::instruction::BumpInstruction;
+use crate::common::version_extension::BumpType;
 use std::rc::Rc;
 
 #[derive(Debug, Clone)]
 pub struct BumpNode {
     pub stable: Option<BumpInstruction>,
     pub prerelease: Option<BumpInstruction>,
+    /// Packages whose bumps were caused by this one
     pub children: Vec<Rc<BumpNode>>,
+    /// Dependency whose bump decided the stable bump, None if it was requested
+    pub stable_cause: Option<String>,
+    /// Dependency whose bump decided the prerelease bump, None if it was requested
+    pub prerelease_cause: Option<String>,
 }
 
 impl PartialEq for BumpNode {
@@ -24,4 +30,11 @@ impl BumpNode {
             panic!("One of stable or prerelease must be set")
         }
     }
+
+    /// Whether either bump is a major bump, i.e. a breaking change.
+    pub fn is_breaking(&self) -> bool {
+        [&self.stable, &self.prerelease]
+            .iter()
+            .any(|i| i.as_ref().is_some_and(|i| i.bump_type() == BumpType::Major))
+    }
 }
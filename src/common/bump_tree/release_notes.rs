@@ -0,0 +1,49 @@
+use super::instruction::BumpInstruction;
+use super::tree::BumpTree;
+use std::fmt::Write;
+
+impl BumpTree<'_> {
+    /// Renders the bumped packages as Markdown release notes, with the breaking changes, i.e.
+    /// packages with a major bump on either release channel, listed apart from the compatible
+    /// ones.
+    pub fn to_release_notes(&self) -> String {
+        let nodes = self.graph_nodes();
+        let mut notes = String::from("# Release notes\n");
+        if nodes.is_empty() {
+            notes.push_str("\nNo packages are bumped.\n");
+            return notes;
+        }
+
+        for (breaking, heading) in [(true, "⚠️ Breaking changes"), (false, "Compatible changes")]
+        {
+            let packages = nodes
+                .iter()
+                .filter(|(_, node)| node.is_breaking() == breaking)
+                .collect::<Vec<_>>();
+            if packages.is_empty() {
+                continue;
+            }
+            writeln!(notes, "\n## {}\n", heading).unwrap();
+            writeln!(notes, "| Package | Stable | Prerelease |").unwrap();
+            writeln!(notes, "| --- | --- | --- |").unwrap();
+            for (name, node) in packages {
+                writeln!(
+                    notes,
+                    "| `{}` | {} | {} |",
+                    name,
+                    version_change(&node.stable),
+                    version_change(&node.prerelease)
+                )
+                .unwrap();
+            }
+        }
+        notes
+    }
+}
+
+fn version_change(instruction: &Option<BumpInstruction>) -> String {
+    match instruction {
+        Some(i) => format!("{} → {}", i.package.borrow().version(), i.next_version),
+        None => "—".to_owned(),
+    }
+}
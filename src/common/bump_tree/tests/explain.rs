@@ -0,0 +1,53 @@
+use crate::common::bump_tree::instruction::BumpInstruction;
+use crate::common::bump_tree::tree::{BumpTree, ReleaseChannel};
+use crate::common::mocks::copy_mock_workspace;
+use crate::common::workspace::Workspace;
+use semver::Version;
+
+#[test]
+fn marks_the_path_which_decided_the_bump() {
+    let stable_workspace =
+        Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+    let prerelease_workspace =
+        Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+    // Released on prerelease, so it will noop; we're not trying to test it here
+    for p in prerelease_workspace.packages.values() {
+        p.borrow_mut().set_version(&Version::new(9, 0, 0));
+    }
+
+    let root_instruction = BumpInstruction::from_str(
+        &stable_workspace,
+        &prerelease_workspace,
+        "x minor",
+        ReleaseChannel::Stable,
+    )
+    .unwrap()
+    .unwrap();
+    let tree = BumpTree::new(
+        &stable_workspace,
+        &prerelease_workspace,
+        vec![root_instruction],
+        ReleaseChannel::Stable,
+    );
+
+    // y is bumped alongside x as a member of its version group, which beats the patch bump x
+    // carries to it
+    let explanation = tree.explain("z").unwrap();
+    assert!(
+        explanation.contains("  stable 0.1.0 -> 0.1.1 (patch), decided by y -> z\n"),
+        "{}",
+        explanation
+    );
+    assert!(
+        explanation.contains("  x ─[stable patch]→ y ─[stable patch]→ z\n"),
+        "{}",
+        explanation
+    );
+    assert!(
+        explanation.contains("  y ─[stable patch]→ z 🏆 highest stable\n"),
+        "{}",
+        explanation
+    );
+
+    assert!(tree.explain("not-a-package").is_err());
+}
@@ -0,0 +1,116 @@
+use crate::common::bump_tree::instruction::BumpInstruction;
+use crate::common::bump_tree::node::BumpNode;
+use crate::common::bump_tree::tree::{BumpTree, ReleaseChannel};
+use crate::common::mocks::{copy_mock_workspace, get_mock_workspaces};
+use crate::common::workspace::Workspace;
+use semver::Version;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+#[test]
+fn marks_significant_edges_and_major_bumps() {
+    let stable_workspace =
+        Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+    let prerelease_workspace =
+        Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+    // Released on prerelease, so it will noop; we're not trying to test it here
+    for p in prerelease_workspace.packages.values() {
+        p.borrow_mut().set_version(&Version::new(9, 0, 0));
+    }
+
+    let root_instruction = BumpInstruction::from_str(
+        &stable_workspace,
+        &prerelease_workspace,
+        "x minor",
+        ReleaseChannel::Stable,
+    )
+    .unwrap()
+    .unwrap();
+    let tree = BumpTree::new(
+        &stable_workspace,
+        &prerelease_workspace,
+        vec![root_instruction],
+        ReleaseChannel::Stable,
+    );
+
+    // y is bumped alongside x as a member of its version group, rather than because it depends
+    // on x, so only the edge to z decided a bump
+    let dot = tree.to_dot();
+    assert!(dot.contains("\"x\" -> \"y\" [style=dashed"), "{}", dot);
+    assert!(dot.contains("\"y\" -> \"z\" [style=bold];"), "{}", dot);
+    assert!(
+        dot.contains("\"y\" [label=\"y\\nstable 1.0.0 -> 1.1.0\\ngroup xy\", color=\"#1f77b4\""),
+        "{}",
+        dot
+    );
+
+    let mermaid = tree.to_mermaid();
+    assert!(mermaid.contains("    x -.-> y\n"), "{}", mermaid);
+    assert!(mermaid.contains("    y ==> z\n"), "{}", mermaid);
+    assert!(mermaid.contains("    class x root\n"), "{}", mermaid);
+}
+
+#[test]
+fn colours_major_bumps() {
+    let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+    let root_instruction = BumpInstruction::from_str(
+        &stable_workspace,
+        &prerelease_workspace,
+        "stable-only-1-0-0 major",
+        ReleaseChannel::Stable,
+    )
+    .unwrap()
+    .unwrap();
+    let tree = BumpTree::new(
+        &stable_workspace,
+        &prerelease_workspace,
+        vec![root_instruction],
+        ReleaseChannel::Stable,
+    );
+
+    assert!(tree
+        .to_dot()
+        .contains("\"stable-only-1-0-0\" [label=\"stable-only-1-0-0\\nstable 1.0.0 -> 2.0.0\", color=\"#d62728\""));
+    assert!(tree
+        .to_mermaid()
+        .contains("    class stable_only_1_0_0 major\n"));
+}
+
+#[test]
+fn marks_edges_significant_on_either_release_channel() {
+    let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+    let node = |name: &str, stable_cause: Option<&str>, prerelease_cause: Option<&str>| {
+        let instruction = |workspace: &Workspace, version: &str| BumpInstruction {
+            package: workspace.packages[name].clone(),
+            next_version: Version::parse(version).unwrap(),
+        };
+        Rc::new(BumpNode {
+            stable: Some(instruction(&stable_workspace, "0.2.0")),
+            prerelease: Some(instruction(&prerelease_workspace, "0.2.0-alpha.1")),
+            children: vec![],
+            stable_cause: stable_cause.map(|c| c.to_owned()),
+            prerelease_cause: prerelease_cause.map(|c| c.to_owned()),
+        })
+    };
+    // b's stable bump was requested, but its prerelease bump was decided by a. c's bumps were
+    // decided by b on both channels, and d's by neither.
+    let nodes = HashMap::from([
+        ("a".to_owned(), node("a", None, None)),
+        ("b".to_owned(), node("b", None, Some("a"))),
+        ("c".to_owned(), node("c", Some("b"), Some("b"))),
+        ("d".to_owned(), node("d", None, None)),
+    ]);
+    let mut tree = BumpTree::new(
+        &stable_workspace,
+        &prerelease_workspace,
+        vec![],
+        ReleaseChannel::Stable,
+    );
+    tree.highest_stable = nodes.clone();
+    tree.highest_prerelease = nodes;
+
+    let dot = tree.to_dot();
+    assert!(dot.contains("\"a\" -> \"b\" [style=bold];"), "{}", dot);
+    assert!(dot.contains("\"b\" -> \"c\" [style=bold];"), "{}", dot);
+    assert!(dot.contains("\"c\" -> \"d\" [style=dashed"), "{}", dot);
+}
@@ -1,4 +1,4 @@
-use crate::common::bump_tree::tests::common::get_mock_workspaces;
+use crate::common::mocks::get_mock_workspaces;
 use crate::common::{bump_tree::instruction::BumpInstruction, version_extension::BumpType};
 use semver::Version;
 use std::str::FromStr;
@@ -56,6 +56,32 @@ pub mod bump_type {
         };
         assert_eq!(patch_bump_instruction.bump_type(), BumpType::Patch);
     }
+
+    #[test]
+    fn handles_prerelease_versions() {
+        let (_, prerelease_workspace) = get_mock_workspaces();
+
+        let package = prerelease_workspace
+            .packages
+            .get("prerelease-only-1-0-0")
+            .unwrap();
+        package
+            .borrow_mut()
+            .set_version(&Version::from_str("1.0.0-alpha.1").unwrap());
+
+        let iteration_instruction = BumpInstruction {
+            package: package.clone(),
+            next_version: Version::from_str("1.0.0-alpha.2").unwrap(),
+        };
+        assert_eq!(iteration_instruction.bump_type(), BumpType::Prerelease);
+
+        // Releasing a prerelease is compatible with it
+        let release_instruction = BumpInstruction {
+            package: package.clone(),
+            next_version: Version::from_str("1.0.0").unwrap(),
+        };
+        assert_eq!(release_instruction.bump_type(), BumpType::Patch);
+    }
 }
 
 pub mod from_str {
@@ -100,30 +126,26 @@ pub mod from_str {
     fn bump_prerelease_only_package_on_stable_channel_fails() {
         let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
 
-        assert!(matches!(
-            BumpInstruction::from_str(
-                &stable_workspace,
-                &prerelease_workspace,
-                "prerelease-only-1-0-0 patch",
-                ReleaseChannel::Stable,
-            ),
-            Err(_)
-        ));
+        assert!(BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-only-1-0-0 patch",
+            ReleaseChannel::Stable,
+        )
+        .is_err());
     }
 
     #[test]
     fn bump_stable_only_package_on_prerelease_channel_fails() {
         let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
 
-        assert!(matches!(
-            BumpInstruction::from_str(
-                &stable_workspace,
-                &prerelease_workspace,
-                "stable-only-1-0-0 patch",
-                ReleaseChannel::Prerelease,
-            ),
-            Err(_)
-        ));
+        assert!(BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "stable-only-1-0-0 patch",
+            ReleaseChannel::Prerelease,
+        )
+        .is_err());
     }
 
     #[test]
@@ -326,3 +348,243 @@ pub mod from_str {
         );
     }
 }
+
+pub mod prerelease_identifier {
+    use crate::common::bump_tree::{
+        instruction::compute_prerelease_bump_instruction, tree::ReleaseChannel,
+    };
+    use semver::Prerelease;
+
+    use super::*;
+
+    #[test]
+    fn uses_configured_identifier() {
+        let (stable_workspace, mut prerelease_workspace) = get_mock_workspaces();
+        prerelease_workspace.prerelease_identifier = Prerelease::new("beta").unwrap();
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-matches-stable-1-0-0 minor",
+            ReleaseChannel::Prerelease,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("1.1.0-beta").unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_existing_identifier() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let package = prerelease_workspace.packages.get("a").unwrap();
+        stable_workspace
+            .packages
+            .get("a")
+            .unwrap()
+            .borrow_mut()
+            .set_version(&Version::from_str("1.0.0").unwrap());
+        package
+            .borrow_mut()
+            .set_version(&Version::from_str("1.0.1-rc.1").unwrap());
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "a minor",
+            ReleaseChannel::Prerelease,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("1.1.0-rc").unwrap()
+        );
+    }
+
+    #[test]
+    fn propagation_keeps_existing_identifier() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let stable_package = stable_workspace.packages.get("a").unwrap();
+        let prerelease_package = prerelease_workspace.packages.get("a").unwrap();
+        stable_package
+            .borrow_mut()
+            .set_version(&Version::from_str("1.0.0").unwrap());
+        prerelease_package
+            .borrow_mut()
+            .set_version(&Version::from_str("1.1.0-rc").unwrap());
+
+        let instruction = compute_prerelease_bump_instruction(
+            Some(prerelease_package),
+            Some(stable_package),
+            Some(&BumpInstruction {
+                package: stable_package.clone(),
+                next_version: Version::from_str("2.0.0").unwrap(),
+            }),
+            None,
+            &Prerelease::new("alpha").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("3.0.0-rc").unwrap()
+        );
+    }
+}
+
+pub mod prerelease_iteration {
+    use crate::common::bump_tree::tree::ReleaseChannel;
+
+    use super::*;
+
+    #[test]
+    fn bumps_iteration() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-ahead-minor prerelease",
+            ReleaseChannel::Prerelease,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("1.1.0-alpha.1").unwrap()
+        );
+        assert_eq!(instruction.bump_type(), BumpType::Prerelease);
+    }
+
+    #[test]
+    fn bumps_prerelease_only_package() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        prerelease_workspace
+            .packages
+            .get("prerelease-only-1-0-0")
+            .unwrap()
+            .borrow_mut()
+            .set_version(&Version::from_str("1.0.0-rc.3").unwrap());
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-only-1-0-0 prerelease",
+            ReleaseChannel::Prerelease,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("1.0.0-rc.4").unwrap()
+        );
+    }
+
+    #[test]
+    fn fails_when_not_a_prerelease() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+
+        assert!(BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-matches-stable-1-0-0 prerelease",
+            ReleaseChannel::Prerelease,
+        )
+        .is_err());
+        assert!(BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "stable-only-1-0-0 prerelease",
+            ReleaseChannel::Stable,
+        )
+        .is_err());
+    }
+}
+
+pub mod explicit_version {
+    use crate::common::bump_tree::instruction::ParsedInstruction;
+    use crate::common::bump_tree::tree::ReleaseChannel;
+
+    use super::*;
+
+    #[test]
+    fn bumps_to_version() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "stable-only-1-0-0 =3.2.1",
+            ReleaseChannel::Stable,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("3.2.1").unwrap()
+        );
+        assert_eq!(instruction.bump_type(), BumpType::Major);
+
+        let instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "prerelease-ahead-minor =1.1.0-rc.1",
+            ReleaseChannel::Prerelease,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            instruction.next_version,
+            Version::from_str("1.1.0-rc.1").unwrap()
+        );
+        assert_eq!(instruction.bump_type(), BumpType::Prerelease);
+    }
+
+    #[test]
+    fn noop_when_already_at_version() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+
+        assert_eq!(
+            BumpInstruction::from_str(
+                &stable_workspace,
+                &prerelease_workspace,
+                "stable-only-1-0-0 =1.0.0",
+                ReleaseChannel::Stable,
+            ),
+            Ok(None)
+        );
+        match BumpInstruction::parse(
+            &stable_workspace,
+            &prerelease_workspace,
+            "stable-only-1-0-0 =1.0.0",
+            ReleaseChannel::Stable,
+        ) {
+            Ok(ParsedInstruction::Unnecessary(reason)) => assert_eq!(
+                reason,
+                "Package stable-only-1-0-0 is already v1.0.0 on branch no-git, so there is no need to bump it"
+            ),
+            other => panic!("Expected the bump to be unnecessary, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_downgrades_and_invalid_versions() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+
+        for s in [
+            "stable-only-1-0-0 =0.9.0",
+            "stable-only-1-0-0 =1.0.0-alpha",
+            "stable-only-1-0-0 =2",
+        ] {
+            assert!(BumpInstruction::from_str(
+                &stable_workspace,
+                &prerelease_workspace,
+                s,
+                ReleaseChannel::Stable,
+            )
+            .is_err());
+        }
+    }
+}
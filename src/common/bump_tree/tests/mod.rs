@@ -1,3 +1,5 @@
-pub(crate) mod common;
+pub mod explain;
+pub mod graph;
 pub mod instruction;
+pub mod release_notes;
 pub mod tree;
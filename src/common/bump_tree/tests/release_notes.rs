@@ -0,0 +1,45 @@
+use crate::common::bump_tree::instruction::BumpInstruction;
+use crate::common::bump_tree::tree::{BumpTree, ReleaseChannel};
+use crate::common::mocks::get_mock_workspaces;
+
+#[test]
+fn groups_breaking_and_compatible_changes() {
+    let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+    let root_instructions = ["stable-only-1-0-0 major", "stable-only-1-1-0 minor"]
+        .iter()
+        .map(|s| {
+            BumpInstruction::from_str(
+                &stable_workspace,
+                &prerelease_workspace,
+                s,
+                ReleaseChannel::Stable,
+            )
+            .unwrap()
+            .unwrap()
+        })
+        .collect();
+    let tree = BumpTree::new(
+        &stable_workspace,
+        &prerelease_workspace,
+        root_instructions,
+        ReleaseChannel::Stable,
+    );
+
+    assert_eq!(
+        tree.to_release_notes(),
+        "# Release notes
+
+## ⚠️ Breaking changes
+
+| Package | Stable | Prerelease |
+| --- | --- | --- |
+| `stable-only-1-0-0` | 1.0.0 → 2.0.0 | — |
+
+## Compatible changes
+
+| Package | Stable | Prerelease |
+| --- | --- | --- |
+| `stable-only-1-1-0` | 1.1.0 → 1.2.0 | — |
+"
+    );
+}
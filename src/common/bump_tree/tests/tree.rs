@@ -1,7 +1,10 @@
-use super::common::get_mock_workspaces;
+use crate::common::bump_tree::node::BumpNode;
 use crate::common::bump_tree::tree::BumpTree;
 use crate::common::bump_tree::{instruction::BumpInstruction, tree::ReleaseChannel};
+use crate::common::mocks::{copy_mock_workspace, get_mock_workspaces};
+use crate::common::workspace::Workspace;
 use semver::Version;
+use std::rc::Rc;
 use std::str::FromStr;
 
 #[derive(Clone)]
@@ -24,25 +27,23 @@ fn run_bump_tree_assertion(
 
     // Set initial versions
     for version_change_assertion in version_change_assertions.clone() {
-        version_change_assertion.initial_stable_version.map(|v| {
+        if let Some(v) = version_change_assertion.initial_stable_version {
             let name = version_change_assertion.package_name.clone();
             let p = stable_workspace.packages.get(&name).unwrap();
             p.borrow_mut().set_version(&v);
-        });
-        version_change_assertion
-            .initial_prerelease_version
-            .map(|v| {
-                let name = version_change_assertion.package_name.clone();
-                let p = prerelease_workspace.packages.get(&name).unwrap();
-                p.borrow_mut().set_version(&v);
-            });
+        }
+        if let Some(v) = version_change_assertion.initial_prerelease_version {
+            let name = version_change_assertion.package_name.clone();
+            let p = prerelease_workspace.packages.get(&name).unwrap();
+            p.borrow_mut().set_version(&v);
+        }
     }
 
     // Build bump tree
     let root_nodes = match BumpInstruction::from_str(
         &stable_workspace,
         &prerelease_workspace,
-        &raw_bump_instruction,
+        raw_bump_instruction,
         release_channel,
     )
     .unwrap()
@@ -342,6 +343,70 @@ pub mod prerelease {
             );
         }
     }
+
+    pub mod prerelease_iteration {
+        use super::*;
+
+        #[test]
+        fn causes_dependents_to_iterate() {
+            run_bump_tree_assertion(
+                "a prerelease",
+                vec![
+                    VersionChangeAssertion {
+                        package_name: "a".to_owned(),
+                        initial_stable_version: Some(Version::from_str("1.0.0").unwrap()),
+                        initial_prerelease_version: Some(
+                            Version::from_str("1.1.0-alpha.1").unwrap(),
+                        ),
+                        expected_stable_version: None,
+                        expected_prerelease_version: Some(
+                            Version::from_str("1.1.0-alpha.2").unwrap(),
+                        ),
+                    },
+                    VersionChangeAssertion {
+                        package_name: "b".to_owned(),
+                        initial_stable_version: Some(Version::from_str("0.1.0").unwrap()),
+                        initial_prerelease_version: Some(Version::from_str("0.1.1-alpha").unwrap()),
+                        expected_stable_version: None,
+                        expected_prerelease_version: Some(
+                            Version::from_str("0.1.1-alpha.1").unwrap(),
+                        ),
+                    },
+                ],
+                ReleaseChannel::Prerelease,
+            );
+        }
+
+        #[test]
+        fn causes_unreleased_dependents_to_patch() {
+            run_bump_tree_assertion(
+                "a prerelease",
+                vec![
+                    VersionChangeAssertion {
+                        package_name: "a".to_owned(),
+                        initial_stable_version: Some(Version::from_str("1.0.0").unwrap()),
+                        initial_prerelease_version: Some(
+                            Version::from_str("1.1.0-alpha.1").unwrap(),
+                        ),
+                        expected_stable_version: None,
+                        expected_prerelease_version: Some(
+                            Version::from_str("1.1.0-alpha.2").unwrap(),
+                        ),
+                    },
+                    VersionChangeAssertion {
+                        package_name: "b".to_owned(),
+                        initial_stable_version: Some(Version::from_str("0.1.0").unwrap()),
+                        initial_prerelease_version: Some(Version::from_str("0.1.0").unwrap()),
+                        expected_stable_version: None,
+                        expected_prerelease_version: Some(
+                            Version::from_str("0.1.1-alpha").unwrap(),
+                        ),
+                    },
+                ],
+                ReleaseChannel::Prerelease,
+            );
+        }
+    }
 }
 
 pub mod stable {
@@ -431,6 +496,34 @@ pub mod stable {
                 ReleaseChannel::Stable,
             );
         }
+
+        #[test]
+        fn causes_renamed_dependents_to_major_bump() {
+            run_bump_tree_assertion(
+                "c major",
+                vec![
+                    VersionChangeAssertion {
+                        package_name: "c".to_owned(),
+                        initial_stable_version: Some(Version::from_str("1.0.0").unwrap()),
+                        expected_stable_version: Some(Version::from_str("2.0.0").unwrap()),
+                        // set this to major version above so it will noop; we're not trying to
+                        // test it here
+                        initial_prerelease_version: Some(Version::from_str("3.0.0-alpha").unwrap()),
+                        expected_prerelease_version: None,
+                    },
+                    VersionChangeAssertion {
+                        package_name: "d".to_owned(),
+                        initial_stable_version: Some(Version::from_str("0.1.0").unwrap()),
+                        expected_stable_version: Some(Version::from_str("0.2.0").unwrap()),
+                        // set this to major version above so it will noop; we're not trying to
+                        // test it here
+                        initial_prerelease_version: Some(Version::from_str("1.0.0-alpha").unwrap()),
+                        expected_prerelease_version: None,
+                    },
+                ],
+                ReleaseChannel::Stable,
+            );
+        }
     }
 
     pub mod minor {
@@ -620,3 +713,227 @@ pub mod stable {
         }
     }
 }
+
+pub mod explicit_version {
+    use super::*;
+
+    #[test]
+    fn propagates_to_dependents() {
+        run_bump_tree_assertion(
+            "a =3.0.0",
+            vec![
+                VersionChangeAssertion {
+                    package_name: "a".to_owned(),
+                    initial_stable_version: Some(Version::from_str("1.0.0").unwrap()),
+                    expected_stable_version: Some(Version::from_str("3.0.0").unwrap()),
+                    // set this to major version above so it will noop; we're not trying to
+                    // test it here
+                    initial_prerelease_version: Some(Version::from_str("4.0.0-alpha").unwrap()),
+                    expected_prerelease_version: None,
+                },
+                VersionChangeAssertion {
+                    package_name: "b".to_owned(),
+                    initial_stable_version: Some(Version::from_str("0.1.0").unwrap()),
+                    expected_stable_version: Some(Version::from_str("0.2.0").unwrap()),
+                    // set this to major version above so it will noop; we're not trying to
+                    // test it here
+                    initial_prerelease_version: Some(Version::from_str("1.0.0-alpha").unwrap()),
+                    expected_prerelease_version: None,
+                },
+            ],
+            ReleaseChannel::Stable,
+        );
+    }
+}
+
+pub mod version_groups {
+    use super::*;
+
+    #[test]
+    fn moves_members_and_their_dependents_together() {
+        let stable_workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        let prerelease_workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        // Released on prerelease, so it will noop; we're not trying to test it here
+        for name in ["x", "y", "z"] {
+            let p = prerelease_workspace.packages.get(name).unwrap();
+            p.borrow_mut()
+                .set_version(&Version::from_str("9.0.0").unwrap());
+        }
+
+        let root_instruction = BumpInstruction::from_str(
+            &stable_workspace,
+            &prerelease_workspace,
+            "x minor",
+            ReleaseChannel::Stable,
+        )
+        .unwrap()
+        .unwrap();
+        let tree = BumpTree::new(
+            &stable_workspace,
+            &prerelease_workspace,
+            vec![root_instruction],
+            ReleaseChannel::Stable,
+        );
+
+        for (name, expected) in [("x", "1.1.0"), ("y", "1.1.0"), ("z", "0.1.1")] {
+            assert_eq!(
+                tree.highest_stable
+                    .get(name)
+                    .and_then(|n| n.stable.as_ref())
+                    .map(|i| i.next_version.clone()),
+                Some(Version::from_str(expected).unwrap()),
+                "{}",
+                name
+            );
+        }
+        assert!(tree.to_string().contains("y 🔗 xy"));
+    }
+}
+
+pub mod propagation {
+    use super::*;
+
+    #[test]
+    fn nests_each_package_once_under_its_cause() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        for (name, version) in [
+            ("a", "1.0.0"),
+            ("b", "0.1.0"),
+            ("c", "2.3.1"),
+            ("d", "0.1.0"),
+        ] {
+            let p = stable_workspace.packages.get(name).unwrap();
+            p.borrow_mut()
+                .set_version(&Version::from_str(version).unwrap());
+        }
+
+        let root_instructions = ["a major", "b minor"]
+            .iter()
+            .map(|s| {
+                BumpInstruction::from_str(
+                    &stable_workspace,
+                    &prerelease_workspace,
+                    s,
+                    ReleaseChannel::Stable,
+                )
+                .unwrap()
+                .unwrap()
+            })
+            .collect();
+        let tree = BumpTree::new(
+            &stable_workspace,
+            &prerelease_workspace,
+            root_instructions,
+            ReleaseChannel::Stable,
+        );
+
+        // b is bumped as much by request as by a, so is kept as a root with c and d beneath it
+        let names =
+            |nodes: &Vec<Rc<BumpNode>>| nodes.iter().map(|n| n.package_name()).collect::<Vec<_>>();
+        assert_eq!(names(&tree.root_nodes), vec!["a", "b"]);
+        assert!(tree.root_nodes[0].children.is_empty());
+        let b = &tree.root_nodes[1];
+        assert_eq!(names(&b.children), vec!["c"]);
+        assert_eq!(names(&b.children[0].children), vec!["d"]);
+        assert_eq!(
+            tree.highest_stable["d"]
+                .stable
+                .as_ref()
+                .unwrap()
+                .next_version,
+            Version::from_str("0.2.0").unwrap()
+        );
+    }
+}
+
+pub mod generated_graph {
+    use super::*;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+    use tempdir::TempDir;
+
+    /// Writes a workspace of `depth` layers of `width` packages, each depending on the first
+    /// package and the package in the same position of the layer before it.
+    fn layered_workspace(depth: usize, width: usize) -> PathBuf {
+        let path = TempDir::new("workspace-version-tools-test")
+            .unwrap()
+            .into_path();
+        let name = |layer: usize, i: usize| format!("p{}-{}", layer, i);
+
+        let mut members = vec![];
+        for layer in 0..depth {
+            for i in 0..width {
+                let dependencies = match layer {
+                    0 => vec![],
+                    _ => BTreeSet::from([0, i])
+                        .into_iter()
+                        .map(|d| format!("{0} = {{ path = \"../{0}\" }}\n", name(layer - 1, d)))
+                        .collect(),
+                };
+                let package_path = path.join(name(layer, i));
+                fs::create_dir_all(package_path.join("src")).unwrap();
+                fs::write(package_path.join("src/lib.rs"), "").unwrap();
+                fs::write(
+                    package_path.join("Cargo.toml"),
+                    format!(
+                        "[package]\nname = \"{}\"\nversion = \"1.0.0\"\nedition = \"2021\"\n\n[dependencies]\n{}",
+                        name(layer, i),
+                        dependencies.concat()
+                    ),
+                )
+                .unwrap();
+                members.push(format!("\"{}\"", name(layer, i)));
+            }
+        }
+        fs::write(
+            path.join("Cargo.toml"),
+            format!(
+                "[workspace]\nresolver = \"2\"\nmembers = [{}]\n",
+                members.join(", ")
+            ),
+        )
+        .unwrap();
+
+        path
+    }
+
+    #[test]
+    fn propagates_through_a_wide_and_deep_graph() {
+        let (depth, width) = (50, 40);
+        // Loading the workspace dominates the test, so both release channels share it
+        let workspace = Workspace::new_test_workspace(layered_workspace(depth, width)).unwrap();
+        let (stable_workspace, prerelease_workspace) = (&workspace, &workspace);
+
+        let root_instruction = BumpInstruction::from_str(
+            stable_workspace,
+            prerelease_workspace,
+            "p0-0 major",
+            ReleaseChannel::Stable,
+        )
+        .unwrap()
+        .unwrap();
+        let started = Instant::now();
+        let tree = BumpTree::new(
+            stable_workspace,
+            prerelease_workspace,
+            vec![root_instruction],
+            ReleaseChannel::Stable,
+        );
+        let elapsed = started.elapsed();
+
+        // Everything but the rest of the first layer depends on p0-0, and is nested exactly once
+        fn count(node: &Rc<BumpNode>) -> usize {
+            1 + node.children.iter().map(count).sum::<usize>()
+        }
+        assert_eq!(tree.highest_stable.len(), (depth - 1) * width + 1);
+        assert_eq!(
+            tree.root_nodes.iter().map(count).sum::<usize>(),
+            tree.highest_stable.len()
+        );
+        assert!(elapsed < Duration::from_secs(2), "took {:?}", elapsed);
+    }
+}
@@ -6,11 +6,14 @@ use crate::common::version_extension::VersionExtension;
 use crate::common::version_extension::{BumpType, EndUserInitiated};
 use crate::common::workspace::Workspace;
 use core::fmt;
-use std::collections::{HashMap, HashSet};
+use semver::Prerelease;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Formatter;
 use std::{cell::RefCell, rc::Rc};
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ReleaseChannel {
     Stable,
     Prerelease,
@@ -24,6 +27,19 @@ pub struct BumpTree<'a> {
     prerelease_workspace: &'a Workspace,
 }
 
+/// A bump made to a package because it was requested, rather than because of a dependency
+type RootBump = (Option<BumpInstruction>, Option<BumpInstruction>);
+
+/// The highest bumps computed for a package, and the dependency which caused them
+struct PackageBump {
+    stable: Option<BumpInstruction>,
+    prerelease: Option<BumpInstruction>,
+    /// None when the bump was requested directly
+    cause: Option<String>,
+    stable_cause: Option<String>,
+    prerelease_cause: Option<String>,
+}
+
 impl<'a> BumpTree<'a> {
     pub fn new(
         stable_workspace: &'a Workspace,
@@ -39,102 +55,242 @@ impl<'a> BumpTree<'a> {
             prerelease_workspace,
         };
 
-        let root_nodes: Vec<_> = root_instructions
+        let mut root_bumps: Vec<RootBump> = root_instructions
             .into_iter()
             .map(|i| match release_channel {
-                ReleaseChannel::Prerelease => tree.new_node(None, Some(i)),
-                ReleaseChannel::Stable => tree.new_node(
-                    Some(i.clone()),
-                    compute_prerelease_bump_instruction(
-                        prerelease_workspace
-                            .packages
-                            .get(&i.package.borrow().name()),
-                        stable_workspace.packages.get(&i.package.borrow().name()),
-                        Some(&i),
-                        None,
-                    ),
-                ),
+                ReleaseChannel::Prerelease => (None, Some(i)),
+                ReleaseChannel::Stable => tree.stable_root_bump(i),
             })
             .collect();
 
-        tree.set_root_nodes(root_nodes);
+        // Version groups are bumped as roots of their own so their dependents are bumped as
+        // usual, which is repeated until no group has members left behind.
+        loop {
+            tree.propagate(&root_bumps);
+            let group_bumps = tree.version_group_bumps();
+            if group_bumps.is_empty() {
+                break;
+            }
+            root_bumps.extend(group_bumps);
+        }
+
         tree
     }
 
-    fn set_root_nodes(&mut self, root_nodes: Vec<Rc<BumpNode>>) {
-        self.root_nodes = root_nodes;
+    fn stable_root_bump(&self, i: BumpInstruction) -> RootBump {
+        let name = i.package.borrow().name();
+        let prerelease_bump_instruction = compute_prerelease_bump_instruction(
+            self.prerelease_workspace.packages.get(&name),
+            self.stable_workspace.packages.get(&name),
+            Some(&i),
+            None,
+            &self.prerelease_workspace.prerelease_identifier,
+        );
+        (Some(i), prerelease_bump_instruction)
     }
 
-    pub fn new_node(
-        &mut self,
-        stable_bump_instruction: Option<BumpInstruction>,
-        prerelease_bump_instruction: Option<BumpInstruction>,
-    ) -> Rc<BumpNode> {
-        // Derive children
-        let unique_children: HashSet<String> = stable_bump_instruction
-            .iter()
-            .chain(prerelease_bump_instruction.iter())
-            .flat_map(|b| b.package.borrow().direct_workspace_dependents())
-            .map(|dependent| dependent.borrow().name())
-            .collect();
+    /// Root bumps moving every member of a version group with a bumped package to the same next
+    /// version.
+    fn version_group_bumps(&self) -> Vec<RootBump> {
+        let stable_instructions =
+            lockstep_instructions(self.stable_workspace, &self.highest_stable, |n| {
+                n.stable.as_ref()
+            });
+        let prerelease_instructions =
+            lockstep_instructions(self.prerelease_workspace, &self.highest_prerelease, |n| {
+                n.prerelease.as_ref()
+            });
 
-        let children_nodes = unique_children
+        stable_instructions
             .into_iter()
-            .map(|name| {
-                self.derive_child_node(
-                    stable_bump_instruction.as_ref(),
-                    prerelease_bump_instruction.as_ref(),
-                    self.stable_workspace.packages.get(&name),
-                    self.prerelease_workspace.packages.get(&name),
-                )
-            })
-            .collect();
+            .map(|i| self.stable_root_bump(i))
+            .chain(prerelease_instructions.into_iter().map(|i| (None, Some(i))))
+            .collect()
+    }
 
-        let bump_node = Rc::new(BumpNode {
-            stable: stable_bump_instruction.clone(),
-            prerelease: prerelease_bump_instruction.clone(),
-            children: children_nodes,
-        });
-
-        // Update keeping track of the highest bumps we've seen for each package
-        if let Some(stable_bump_instruction) = stable_bump_instruction {
-            let name = stable_bump_instruction.package.borrow().name();
-            self.highest_stable
-                .entry(name.clone())
-                .and_modify(|e| {
-                    if e.stable.as_ref().map(|i| i.bump_type())
-                        < Some(stable_bump_instruction.bump_type())
-                    {
-                        *e = bump_node.clone()
-                    }
-                })
-                .or_insert(bump_node.clone());
+    /// Computes the highest bump of every package in a single pass over the workspace graph, in
+    /// topological order so every dependency's highest bump is known before its dependents'.
+    fn propagate(&mut self, root_bumps: &[RootBump]) {
+        let order = self.topological_order();
+        let mut bumps: HashMap<String, PackageBump> = HashMap::new();
+
+        for name in order.iter() {
+            let mut candidates = root_bumps
+                .iter()
+                .filter(|b| root_bump_name(b) == *name)
+                .map(|(stable, prerelease)| (None, stable.clone(), prerelease.clone()))
+                .collect::<Vec<_>>();
+            for dependency in self.dependencies(name) {
+                if let Some(bump) = bumps.get(&dependency) {
+                    let (stable, prerelease) = self.derive_child_bump(
+                        bump.stable.as_ref(),
+                        bump.prerelease.as_ref(),
+                        self.stable_workspace.packages.get(name),
+                        self.prerelease_workspace.packages.get(name),
+                    );
+                    candidates.push((Some(dependency), stable, prerelease));
+                }
+            }
+
+            let stable = highest_candidate(candidates.iter().map(|(c, s, _)| (c, s)));
+            let prerelease = highest_candidate(candidates.iter().map(|(c, _, p)| (c, p)));
+            let cause = match (&stable, &prerelease) {
+                (Some((cause, _)), _) | (None, Some((cause, _))) => (*cause).clone(),
+                (None, None) => continue,
+            };
+            bumps.insert(
+                name.clone(),
+                PackageBump {
+                    stable: stable.map(|(_, i)| i.clone()),
+                    prerelease: prerelease.map(|(_, i)| i.clone()),
+                    cause,
+                    stable_cause: stable.and_then(|(c, _)| c.clone()),
+                    prerelease_cause: prerelease.and_then(|(c, _)| c.clone()),
+                },
+            );
         }
 
-        if let Some(prerelease_bump_instruction) = prerelease_bump_instruction {
-            let name = prerelease_bump_instruction.package.borrow().name();
-            self.highest_prerelease
-                .entry(name.clone())
-                .and_modify(|e| {
-                    if e.prerelease.as_ref().map(|i| i.bump_type())
-                        < Some(prerelease_bump_instruction.bump_type())
-                    {
-                        *e = bump_node.clone()
-                    }
-                })
-                .or_insert(bump_node.clone());
+        // Dependents by the dependency which caused their bump, in topological order
+        let mut caused: HashMap<&String, Vec<&String>> = HashMap::new();
+        for name in order.iter() {
+            if let Some(cause) = bumps.get(name).and_then(|b| b.cause.as_ref()) {
+                caused.entry(cause).or_default().push(name);
+            }
+        }
+
+        // Build the nodes from the leaves up, each under the dependency which caused its bump
+        let mut nodes: HashMap<String, Rc<BumpNode>> = HashMap::new();
+        for name in order.iter().rev() {
+            let Some(bump) = bumps.get(name) else {
+                continue;
+            };
+            let children = caused
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|n| nodes[*n].clone())
+                .collect();
+            nodes.insert(
+                name.clone(),
+                Rc::new(BumpNode {
+                    stable: bump.stable.clone(),
+                    prerelease: bump.prerelease.clone(),
+                    children,
+                    stable_cause: bump.stable_cause.clone(),
+                    prerelease_cause: bump.prerelease_cause.clone(),
+                }),
+            );
+        }
+
+        let mut root_names: Vec<String> = vec![];
+        for name in root_bumps.iter().map(root_bump_name) {
+            if !root_names.contains(&name) && bumps.get(&name).is_some_and(|b| b.cause.is_none()) {
+                root_names.push(name);
+            }
+        }
+        self.root_nodes = root_names.iter().map(|n| nodes[n].clone()).collect();
+        self.highest_stable = nodes
+            .iter()
+            .filter(|(_, n)| n.stable.is_some())
+            .map(|(name, n)| (name.clone(), n.clone()))
+            .collect();
+        self.highest_prerelease = nodes
+            .iter()
+            .filter(|(_, n)| n.prerelease.is_some())
+            .map(|(name, n)| (name.clone(), n.clone()))
+            .collect();
+    }
+
+    /// Bumps a dependency's highest bumps cause on a dependent.
+    pub(super) fn carried_bump(&self, dependency: &BumpNode, dependent: &str) -> RootBump {
+        self.derive_child_bump(
+            dependency.stable.as_ref(),
+            dependency.prerelease.as_ref(),
+            self.stable_workspace.packages.get(dependent),
+            self.prerelease_workspace.packages.get(dependent),
+        )
+    }
+
+    /// Version group of a package on either release channel.
+    pub(super) fn version_group(&self, name: &str) -> Option<&str> {
+        self.stable_workspace
+            .version_group(name)
+            .or_else(|| self.prerelease_workspace.version_group(name))
+    }
+
+    /// Direct dependencies of a package on either release channel.
+    pub(super) fn dependencies(&self, name: &str) -> BTreeSet<String> {
+        [self.stable_workspace, self.prerelease_workspace]
+            .iter()
+            .filter_map(|w| w.packages.get(name))
+            .flat_map(|p| p.borrow().direct_workspace_dependencies().clone())
+            .collect()
+    }
+
+    /// Names of the packages on either release channel, ordered so dependencies come before their
+    /// dependents.
+    fn topological_order(&self) -> Vec<String> {
+        let names = self
+            .stable_workspace
+            .packages
+            .keys()
+            .chain(self.prerelease_workspace.packages.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        let mut visiting = HashSet::new();
+        for name in names {
+            self.visit(name, &mut visited, &mut visiting, &mut order);
         }
+        // Dependents were visited before the packages they depend on
+        order.reverse();
+        order
+    }
+
+    /// Direct dependents of a package on either release channel.
+    pub(super) fn dependents(&self, name: &str) -> BTreeSet<String> {
+        [self.stable_workspace, self.prerelease_workspace]
+            .iter()
+            .filter_map(|w| w.packages.get(name))
+            .flat_map(|p| p.borrow().direct_workspace_dependents())
+            .map(|dependent| dependent.borrow().name())
+            .collect()
+    }
 
-        bump_node
+    fn visit(
+        &self,
+        name: String,
+        visited: &mut HashSet<String>,
+        visiting: &mut HashSet<String>,
+        order: &mut Vec<String>,
+    ) {
+        if visited.contains(&name) {
+            return;
+        }
+        if !visiting.insert(name.clone()) {
+            log::warn!(
+                "⚠️ {} is part of a dependency cycle across the release channels, ignoring the edge back to it",
+                name
+            );
+            return;
+        }
+        for dependent in self.dependents(&name) {
+            self.visit(dependent, visited, visiting, order);
+        }
+        visiting.remove(&name);
+        visited.insert(name.clone());
+        order.push(name);
     }
 
-    pub fn derive_child_node(
-        &mut self,
+    fn derive_child_bump(
+        &self,
         stable_parent_bump_instruction: Option<&BumpInstruction>,
         prerelease_parent_bump_instruction: Option<&BumpInstruction>,
         stable_child_package: Option<&Rc<RefCell<Package>>>,
         prerelease_child_package: Option<&Rc<RefCell<Package>>>,
-    ) -> Rc<BumpNode> {
+    ) -> RootBump {
         // Child stable bump type can be derived from the parent alone.
         //
         // If there's no parent bump, or no child package, the child bump type is just None.
@@ -150,10 +306,12 @@ impl<'a> BumpTree<'a> {
                         next_version: cur_version.bump(BumpType::Major, EndUserInitiated::No),
                     }),
                     // Parent compatible change
-                    BumpType::Minor | BumpType::Patch => Some(BumpInstruction {
-                        package: stable_child_package.clone(),
-                        next_version: cur_version.bump(BumpType::Patch, EndUserInitiated::No),
-                    }),
+                    BumpType::Minor | BumpType::Patch | BumpType::Prerelease => {
+                        Some(BumpInstruction {
+                            package: stable_child_package.clone(),
+                            next_version: cur_version.bump(BumpType::Patch, EndUserInitiated::No),
+                        })
+                    }
                 }
             } else {
                 None
@@ -164,9 +322,10 @@ impl<'a> BumpTree<'a> {
             stable_child_package,
             stable_bump_instruction.as_ref(),
             prerelease_parent_bump_instruction,
+            &self.prerelease_workspace.prerelease_identifier,
         );
 
-        self.new_node(stable_bump_instruction, prerelease_bump_instruction)
+        (stable_bump_instruction, prerelease_bump_instruction)
     }
 
     pub fn fmt_node(
@@ -210,14 +369,15 @@ impl<'a> BumpTree<'a> {
         } else {
             "".to_string()
         };
+        let name = node.package_name();
+        let group_details = self
+            .version_group(&name)
+            .map(|group| format!(" 🔗 {}", group))
+            .unwrap_or_default();
         write!(
             f,
-            "{}{}{}{}{}",
-            prefix,
-            connector,
-            node.package_name(),
-            stable_bump_details,
-            prerelease_bump_details,
+            "{}{}{}{}{}{}",
+            prefix, connector, name, group_details, stable_bump_details, prerelease_bump_details,
         )?;
 
         let new_prefix = if last {
@@ -225,22 +385,108 @@ impl<'a> BumpTree<'a> {
         } else {
             prefix + "│   "
         };
-        let significant_children = node
-            .children
-            .iter()
-            .filter(|c| {
-                let highest_stable_child = self.highest_stable.get(&c.package_name());
-                let highest_prerelease_child = self.highest_prerelease.get(&c.package_name());
-                highest_stable_child.is_some_and(|highest| Rc::ptr_eq(c, highest))
-                    || highest_prerelease_child.is_some_and(|highest| Rc::ptr_eq(c, highest))
-            })
-            .collect::<Vec<_>>();
-        for (i, dependent) in significant_children.iter().enumerate() {
-            let is_last = i == significant_children.len() - 1;
-            write!(f, "\n")?;
+        for (i, dependent) in node.children.iter().enumerate() {
+            let is_last = i == node.children.len() - 1;
+            writeln!(f)?;
             self.fmt_node(dependent, f, new_prefix.clone(), is_last)?;
         }
 
         Ok(())
     }
 }
+
+fn root_bump_name((stable, prerelease): &RootBump) -> String {
+    stable
+        .as_ref()
+        .or(prerelease.as_ref())
+        .expect("One of stable or prerelease must be set")
+        .package
+        .borrow()
+        .name()
+}
+
+/// The highest of the candidate instructions along with its cause, preferring earlier candidates
+/// on ties.
+fn highest_candidate<'c>(
+    candidates: impl Iterator<Item = (&'c Option<String>, &'c Option<BumpInstruction>)>,
+) -> Option<(&'c Option<String>, &'c BumpInstruction)> {
+    candidates
+        .filter_map(|(cause, i)| i.as_ref().map(|i| (cause, i)))
+        .fold(None, |highest, (cause, i)| match highest {
+            Some((_, h))
+                if (h.bump_type(), &h.next_version) >= (i.bump_type(), &i.next_version) =>
+            {
+                highest
+            }
+            _ => Some((cause, i)),
+        })
+}
+
+/// Instructions bumping the members of each of the workspace's version groups which lag behind the
+/// group's next version, given the highest bumps made so far.
+///
+/// The next version of a group is the highest next version of its bumped members, unless a member
+/// is already at or past it, in which case the highest current version is bumped by the highest
+/// bump type in the group.
+fn lockstep_instructions(
+    workspace: &Workspace,
+    highest: &HashMap<String, Rc<BumpNode>>,
+    instruction: impl Fn(&BumpNode) -> Option<&BumpInstruction>,
+) -> Vec<BumpInstruction> {
+    let mut instructions = vec![];
+    for members in workspace.version_groups.values() {
+        let bumped = members
+            .iter()
+            .filter_map(|m| highest.get(m).and_then(|n| instruction(n)))
+            .collect::<Vec<_>>();
+        let (Some(highest_bump_type), Some(highest_next_version)) = (
+            bumped.iter().map(|i| i.bump_type()).max(),
+            bumped.iter().map(|i| i.next_version.clone()).max(),
+        ) else {
+            continue;
+        };
+
+        let packages = members
+            .iter()
+            .filter_map(|m| workspace.packages.get(m))
+            .collect::<Vec<_>>();
+        let highest_cur_version = packages
+            .iter()
+            .map(|p| p.borrow().version())
+            .max()
+            .expect("version groups have members");
+
+        let iteration = match highest_bump_type {
+            BumpType::Prerelease => highest_cur_version.bump_prerelease_iteration(),
+            _ => None,
+        };
+        let next_version = if highest_cur_version < highest_next_version {
+            highest_next_version
+        } else if let Some(iteration) = iteration {
+            iteration
+        } else {
+            // A released version can't be iterated on, so needs at least a patch bump
+            let bump_type = std::cmp::max(highest_bump_type, BumpType::Patch);
+            let next_version = highest_cur_version.bump(bump_type, EndUserInitiated::No);
+            match highest_next_version.prerelease_identifier() {
+                Some(identifier) => next_version.with_prerelease(&identifier),
+                None => next_version.with_prerelease(&Prerelease::EMPTY),
+            }
+        };
+
+        for package in packages {
+            let name = package.borrow().name();
+            let already_bumped = highest
+                .get(&name)
+                .and_then(|n| instruction(n))
+                .is_some_and(|i| i.next_version == next_version);
+            if !already_bumped {
+                instructions.push(BumpInstruction {
+                    package: package.clone(),
+                    next_version: next_version.clone(),
+                });
+            }
+        }
+    }
+    instructions
+}
@@ -0,0 +1,101 @@
+//! Per-package CHANGELOG.md files, in the Keep a Changelog (https://keepachangelog.com) style.
+
+use semver::Version;
+
+pub const CHANGELOG_FILE_NAME: &str = "CHANGELOG.md";
+
+/// Header of new changelogs
+const HEADER: &str = "# Changelog
+
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+";
+
+/// A changelog section for a release, e.g. "## [1.4.0] - 2024-01-31", listing its entries.
+pub fn section(version: &Version, date: &str, entries: &[String]) -> String {
+    let mut section = format!("## [{}] - {}\n\n", version, date);
+    for entry in entries {
+        // Continuation lines are indented so multi-line entries stay in their list item
+        for (i, line) in entry.lines().enumerate() {
+            let prefix = match (i, line.is_empty()) {
+                (0, _) => "- ",
+                (_, true) => "",
+                (_, false) => "  ",
+            };
+            section.push_str(prefix);
+            section.push_str(line);
+            section.push('\n');
+        }
+    }
+    section
+}
+
+/// Adds a release section to a changelog, creating it if there is none. The section goes above
+/// the latest release, leaving any `## [Unreleased]` section at the top.
+pub fn insert_section(changelog: Option<&str>, section: &str) -> String {
+    let Some(changelog) = changelog else {
+        return format!("{}\n{}", HEADER, section);
+    };
+
+    let mut offset = 0;
+    for line in changelog.split_inclusive('\n') {
+        let is_release =
+            line.starts_with("## ") && !line[3..].trim().to_lowercase().starts_with("[unreleased]");
+        if is_release {
+            return format!(
+                "{}{}\n{}",
+                &changelog[..offset],
+                section,
+                &changelog[offset..]
+            );
+        }
+        offset += line.len();
+    }
+
+    let mut changelog = changelog.trim_end().to_owned();
+    changelog.push_str("\n\n");
+    changelog.push_str(section);
+    changelog
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_sections() {
+        let entries = vec![
+            "Added a call.\n\nIt's fast.".to_owned(),
+            "Updated dependency `x` to v2.0.0".to_owned(),
+        ];
+        assert_eq!(
+            section(&Version::new(1, 4, 0), "2024-01-31", &entries),
+            "## [1.4.0] - 2024-01-31\n\n- Added a call.\n\n  It's fast.\n- Updated dependency `x` to v2.0.0\n"
+        );
+    }
+
+    #[test]
+    fn inserts_sections_above_the_latest_release() {
+        let section = "## [1.1.0] - 2024-01-31\n\n- Added a call.\n";
+
+        let created = insert_section(None, section);
+        assert!(created.starts_with("# Changelog\n"));
+        assert!(created.ends_with(
+            "semver.org/spec/v2.0.0.html).\n\n## [1.1.0] - 2024-01-31\n\n- Added a call.\n"
+        ));
+
+        let existing =
+            "# Changelog\n\n## [Unreleased]\n\n- Soon\n\n## [1.0.0] - 2023-01-01\n\n- Released\n";
+        assert_eq!(
+            insert_section(Some(existing), section),
+            "# Changelog\n\n## [Unreleased]\n\n- Soon\n\n## [1.1.0] - 2024-01-31\n\n- Added a call.\n\n## [1.0.0] - 2023-01-01\n\n- Released\n"
+        );
+
+        assert_eq!(
+            insert_section(Some("# Changelog\n"), section),
+            "# Changelog\n\n## [1.1.0] - 2024-01-31\n\n- Added a call.\n"
+        );
+    }
+}
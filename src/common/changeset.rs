@@ -0,0 +1,234 @@
+//! Changesets are small Markdown files contributors add under `.changes/` alongside their changes,
+//! naming the bumps the changes need in TOML front matter, followed by a description of them:
+//!
+//! ```md
+//! ---
+//! pallet-balances = "minor"
+//! sp-core = "patch"
+//! ---
+//!
+//! Added `transfer_all`, which transfers the whole free balance of an account.
+//! ```
+
+use super::manifest_source::{GitTree, ManifestSource};
+use super::version_extension::BumpType;
+use super::workspace::Workspace;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use toml_edit::Document;
+
+/// Directory changesets are kept in, relative to the workspace root
+pub const CHANGES_DIR: &str = ".changes";
+
+/// A single changeset file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changeset {
+    /// Path relative to the workspace root
+    pub path: PathBuf,
+    /// Bump of each package, by name, as given in a bump instruction, e.g. "minor" or "=2.0.0"
+    pub bumps: BTreeMap<String, String>,
+    /// Description of the changes, for changelogs
+    pub description: String,
+}
+
+impl Changeset {
+    pub fn parse(path: &Path, content: &str) -> Result<Self, String> {
+        let mut lines = content.lines();
+        if lines.next().map(|l| l.trim()) != Some("---") {
+            return Err(format!(
+                "Changeset {:?} must start with a --- line, followed by the bumps",
+                path
+            ));
+        }
+        let mut front_matter = vec![];
+        loop {
+            match lines.next() {
+                Some(l) if l.trim() == "---" => break,
+                Some(l) => front_matter.push(l),
+                None => {
+                    return Err(format!(
+                        "Changeset {:?} is missing the --- line ending its bumps",
+                        path
+                    ))
+                }
+            }
+        }
+
+        let doc = front_matter
+            .join("\n")
+            .parse::<Document>()
+            .map_err(|e| format!("Changeset {:?} has invalid bumps: {}", path, e))?;
+        let mut bumps = BTreeMap::new();
+        for (name, bump) in doc.iter() {
+            let bump = bump.as_str().ok_or_else(|| {
+                format!(
+                    "Changeset {:?} bump of {} must be a string, e.g. \"minor\"",
+                    path, name
+                )
+            })?;
+            bumps.insert(name.to_owned(), bump.trim().to_owned());
+        }
+        if bumps.is_empty() {
+            return Err(format!("Changeset {:?} doesn't bump any packages", path));
+        }
+
+        let description = lines.collect::<Vec<_>>().join("\n").trim().to_owned();
+        if description.is_empty() {
+            return Err(format!("Changeset {:?} has no description", path));
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            bumps,
+            description,
+        })
+    }
+}
+
+/// Reads the changesets committed to the workspace's branch, sorted by path.
+pub fn read_changesets(workspace: &Workspace) -> Result<Vec<Changeset>, String> {
+    let repo = workspace.open_repository();
+    let source = GitTree::new(&repo, &workspace.branch_name)?;
+    let mut files = source
+        .files(Path::new(CHANGES_DIR))?
+        .into_iter()
+        .filter(|f| f.ends_with(".md") && f != "README.md")
+        .collect::<Vec<_>>();
+    files.sort();
+
+    let mut changesets = vec![];
+    for file in files {
+        let path = Path::new(CHANGES_DIR).join(file);
+        let content = source
+            .read(&path)?
+            .expect("changesets are only found by listing the directory");
+        changesets.push(Changeset::parse(&path, &content)?);
+    }
+    Ok(changesets)
+}
+
+/// Merges changesets into a bump instruction for each package, e.g. "pallet-balances minor",
+/// keeping the highest bump type of each. Exact versions must agree between changesets.
+pub fn merge_instructions(changesets: &[Changeset]) -> Result<Vec<String>, String> {
+    let mut merged: BTreeMap<&str, (&str, &Path)> = BTreeMap::new();
+    for changeset in changesets {
+        for (name, bump) in changeset.bumps.iter() {
+            let Some((other, other_path)) = merged.get(name.as_str()).copied() else {
+                merged.insert(name, (bump, &changeset.path));
+                continue;
+            };
+            match (BumpType::from_str(other), BumpType::from_str(bump)) {
+                (Ok(a), Ok(b)) if b > a => {
+                    merged.insert(name, (bump, &changeset.path));
+                }
+                (Ok(_), Ok(_)) => {}
+                _ if other == bump => {}
+                _ => {
+                    return Err(format!(
+                        "Changesets {:?} and {:?} disagree on the bump of {}: {} and {}",
+                        other_path, changeset.path, name, other, bump
+                    ))
+                }
+            }
+        }
+    }
+    Ok(merged
+        .into_iter()
+        .map(|(name, (bump, _))| format!("{} {}", name, bump))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::git::stage_and_commit_all_changes;
+    use crate::common::mocks::init_mock_repository;
+    use git2::Repository;
+    use std::fs;
+
+    fn changeset(path: &str, bumps: &[(&str, &str)]) -> Changeset {
+        Changeset {
+            path: PathBuf::from(path),
+            bumps: bumps
+                .iter()
+                .map(|(n, b)| (n.to_string(), b.to_string()))
+                .collect(),
+            description: "A change".to_owned(),
+        }
+    }
+
+    #[test]
+    fn parses_changesets() {
+        let path = Path::new(".changes/a.md");
+        let parsed = Changeset::parse(
+            path,
+            "---\nx = \"minor\"\ny = \"=2.0.0\"\n---\n\nAdded a call.\n\nIt's fast.\n",
+        )
+        .unwrap();
+        assert_eq!(parsed.bumps["x"], "minor");
+        assert_eq!(parsed.bumps["y"], "=2.0.0");
+        assert_eq!(parsed.description, "Added a call.\n\nIt's fast.");
+
+        for invalid in [
+            "x = \"minor\"\n---\nAdded a call.",
+            "---\nx = \"minor\"\nAdded a call.",
+            "---\nx = 1\n---\nAdded a call.",
+            "---\n---\nAdded a call.",
+            "---\nx = \"minor\"\n---\n",
+        ] {
+            assert!(Changeset::parse(path, invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn merges_the_highest_bump_of_each_package() {
+        let changesets = [
+            changeset(".changes/a.md", &[("x", "patch"), ("y", "=2.0.0")]),
+            changeset(".changes/b.md", &[("x", "major"), ("y", "=2.0.0")]),
+            changeset(".changes/c.md", &[("x", "minor")]),
+        ];
+        assert_eq!(
+            merge_instructions(&changesets).unwrap(),
+            vec!["x major", "y =2.0.0"]
+        );
+
+        let conflicting = [
+            changeset(".changes/a.md", &[("y", "=2.0.0")]),
+            changeset(".changes/b.md", &[("y", "minor")]),
+        ];
+        let err = merge_instructions(&conflicting).unwrap_err();
+        assert!(err.contains("disagree on the bump of y"), "{}", err);
+    }
+
+    #[test]
+    fn consumes_changesets_in_the_bump_commit() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        fs::create_dir(path.join(CHANGES_DIR)).unwrap();
+        fs::write(path.join(".changes/README.md"), "Not a changeset").unwrap();
+        fs::write(
+            path.join(".changes/x.md"),
+            "---\nx = \"minor\"\n---\nAdded a call.\n",
+        )
+        .unwrap();
+        stage_and_commit_all_changes(&repo, "main", "Add a changeset").unwrap();
+
+        let mut workspace = Workspace::new(path.clone(), None, "origin").unwrap();
+        let changesets = read_changesets(&workspace).unwrap();
+        assert_eq!(changesets.len(), 1);
+        assert_eq!(changesets[0].path, Path::new(".changes/x.md"));
+
+        workspace
+            .remove_files(&[changesets[0].path.clone()])
+            .unwrap();
+        assert!(workspace
+            .diff()
+            .unwrap()
+            .contains("--- a/.changes/x.md\n+++ b/.changes/x.md\n@@ -1,4 +0,0 @@"));
+
+        workspace.write_manifests();
+        workspace.stage_and_commit_all("Apply bumps").unwrap();
+        assert!(read_changesets(&workspace).unwrap().is_empty());
+        assert!(path.join(".changes/README.md").exists());
+    }
+}
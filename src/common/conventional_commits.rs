@@ -0,0 +1,173 @@
+use super::git::commits_since;
+use super::version_extension::BumpType;
+use super::workspace::Workspace;
+use std::collections::BTreeMap;
+
+/// The parts of a Conventional Commits (https://www.conventionalcommits.org) message which decide
+/// the bump it needs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    /// e.g. `feat` or `fix`
+    pub kind: String,
+    pub breaking: bool,
+}
+
+impl ConventionalCommit {
+    /// Parses a commit message, or returns None if it doesn't follow the Conventional Commits
+    /// format, e.g. "feat(pallet-balances)!: remove deprecated calls".
+    pub fn parse(message: &str) -> Option<Self> {
+        let header = message.lines().next()?;
+        let (prefix, description) = header.split_once(": ")?;
+        if description.trim().is_empty() {
+            return None;
+        }
+
+        let (prefix, breaking) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+        let kind = match prefix.split_once('(') {
+            Some((kind, scope)) if scope.ends_with(')') && scope.len() > 1 => kind,
+            Some(_) => return None,
+            None => prefix,
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        let breaking_footer = message
+            .lines()
+            .skip(1)
+            .any(|l| l.starts_with("BREAKING CHANGE:") || l.starts_with("BREAKING-CHANGE:"));
+
+        Some(Self {
+            kind: kind.to_lowercase(),
+            breaking: breaking || breaking_footer,
+        })
+    }
+
+    /// Breaking changes need a major bump, features a minor bump and fixes a patch bump. Other
+    /// kinds of commits, e.g. `docs` or `chore`, don't need a bump.
+    pub fn bump_type(&self) -> Option<BumpType> {
+        if self.breaking {
+            return Some(BumpType::Major);
+        }
+        match self.kind.as_str() {
+            "feat" => Some(BumpType::Minor),
+            "fix" => Some(BumpType::Patch),
+            _ => None,
+        }
+    }
+}
+
+/// Bump instructions inferred from the commits on a workspace's branch.
+#[derive(Debug, PartialEq, Eq)]
+pub struct InferredBumps {
+    /// e.g. "pallet-balances minor", one for each package needing a bump, sorted by package
+    pub instructions: Vec<String>,
+    /// Short ids and headers of the commits touching packages which aren't Conventional Commits
+    pub unclassified: Vec<String>,
+}
+
+/// Infers the bump each package needs from the Conventional Commits touching its directory on the
+/// workspace's branch since `since`, keeping the highest bump of each package.
+pub fn infer_bump_instructions(
+    workspace: &Workspace,
+    since: &str,
+) -> Result<InferredBumps, String> {
+    let repo = workspace.open_repository();
+    let commits = commits_since(&repo, since, &workspace.branch_name).map_err(|e| {
+        format!(
+            "Failed to list the commits on {} since {}: {}",
+            workspace.branch_name, since, e
+        )
+    })?;
+
+    let mut highest: BTreeMap<String, BumpType> = BTreeMap::new();
+    let mut unclassified = vec![];
+    for commit in commits {
+        let packages = workspace.packages_owning(&repo, &commit.files)?;
+        if packages.is_empty() {
+            continue;
+        }
+        let Some(conventional_commit) = ConventionalCommit::parse(&commit.message) else {
+            let id = commit.id.to_string();
+            let header = commit.message.lines().next().unwrap_or_default();
+            log::warn!(
+                "⚠️ Commit {} touching {} isn't a Conventional Commit: {}",
+                &id[..7],
+                packages.keys().cloned().collect::<Vec<_>>().join(", "),
+                header
+            );
+            unclassified.push(format!("{} {}", &id[..7], header));
+            continue;
+        };
+        let Some(bump_type) = conventional_commit.bump_type() else {
+            continue;
+        };
+        for name in packages.into_keys() {
+            let bump = highest.entry(name).or_insert(bump_type);
+            *bump = (*bump).max(bump_type);
+        }
+    }
+
+    Ok(InferredBumps {
+        instructions: highest
+            .into_iter()
+            .map(|(name, bump_type)| format!("{} {}", name, bump_type))
+            .collect(),
+        unclassified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::git::stage_and_commit_all_changes;
+    use crate::common::mocks::init_mock_repository;
+    use git2::Repository;
+    use std::fs;
+
+    #[test]
+    fn parses_conventional_commits() {
+        let bump_type = |message: &str| ConventionalCommit::parse(message).map(|c| c.bump_type());
+
+        assert_eq!(bump_type("feat: add a call"), Some(Some(BumpType::Minor)));
+        assert_eq!(bump_type("fix(x): off by one"), Some(Some(BumpType::Patch)));
+        assert_eq!(
+            bump_type("feat(x)!: drop a call"),
+            Some(Some(BumpType::Major))
+        );
+        assert_eq!(
+            bump_type("fix: rename a call\n\nBREAKING CHANGE: the old name is gone"),
+            Some(Some(BumpType::Major))
+        );
+        assert_eq!(bump_type("docs: explain a call"), Some(None));
+        assert_eq!(bump_type("Add a call"), None);
+        assert_eq!(bump_type("feat:"), None);
+        assert_eq!(bump_type("feat(x: add a call"), None);
+    }
+
+    #[test]
+    fn infers_the_highest_bump_of_each_package() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let commit = |file: &str, message: &str| {
+            fs::write(path.join(file), message).unwrap();
+            stage_and_commit_all_changes(&repo, "main", message).unwrap();
+        };
+        commit("x/src/main.rs", "fix: x");
+        commit("x/src/main.rs", "feat: x");
+        commit("y/src/main.rs", "docs: y");
+        commit("z/src/main.rs", "fix!: z");
+        commit("z/src/main.rs", "tweak z");
+        commit("README.md", "not a package");
+
+        let workspace = Workspace::new(path, None, "origin").unwrap();
+        let inferred = infer_bump_instructions(&workspace, "HEAD~6").unwrap();
+
+        assert_eq!(inferred.instructions, vec!["x minor", "z major"]);
+        assert_eq!(inferred.unclassified.len(), 1);
+        assert!(inferred.unclassified[0].ends_with(" tweak z"));
+    }
+}
@@ -0,0 +1,17 @@
+use similar::TextDiff;
+use std::path::Path;
+
+/// A unified diff of the file at `path` going from `old` to `new`, or None if they're the same.
+pub fn unified_diff(path: &Path, old: &str, new: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let path = path.display();
+    Some(
+        TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header(&format!("a/{}", path), &format!("b/{}", path))
+            .to_string(),
+    )
+}
@@ -2,21 +2,22 @@
 ///
 /// Heavy inspiration taken from https://github.com/rust-lang/git2-rs/tree/master/examples
 use git2::{
-    AnnotatedCommit, AutotagOption, BranchType, Commit, FetchOptions, IndexAddOption, ObjectType,
-    PushOptions, Reference, Remote, RemoteCallbacks, Repository,
+    build::CheckoutBuilder, AnnotatedCommit, AutotagOption, BranchType, Commit, Diff, DiffOptions,
+    FetchOptions, IndexAddOption, ObjectType, Oid, PushOptions, Reference, Remote, RemoteCallbacks,
+    Repository, Status,
 };
 use std::{
     fs::File,
     io::{self, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-pub fn get_current_branch_name<'a>(repo: &'a Repository) -> Result<String, String> {
+pub fn get_current_branch_name(repo: &Repository) -> Result<String, String> {
     let head = repo.head().expect("Failed to get HEAD");
 
     if head.is_branch() {
         // If HEAD is pointing to a branch, find its name
-        let shorthand = head.shorthand().ok_or_else(|| "Branch name not found")?;
+        let shorthand = head.shorthand().ok_or("Branch name not found")?;
         Ok(repo
             .find_branch(shorthand, BranchType::Local)
             .expect("Just looked up branch - it must exist!")
@@ -30,21 +31,23 @@ pub fn get_current_branch_name<'a>(repo: &'a Repository) -> Result<String, Strin
     }
 }
 
-pub fn is_working_tree_clean<'a>(repo: &'a Repository) -> bool {
+/// Whether there are no uncommitted changes, staged or not, and no untracked files.
+pub fn is_working_tree_clean(repo: &Repository) -> bool {
     let statuses = repo
         .statuses(None)
         .expect("Failed to get repository statuses");
-    for s in statuses.iter() {
-        match s.status() {
-            git2::Status::WT_NEW
-            | git2::Status::WT_MODIFIED
-            | git2::Status::WT_DELETED
-            | git2::Status::WT_TYPECHANGE
-            | git2::Status::WT_RENAMED => return false,
-            _ => {}
-        }
-    }
-    true
+    let uncommitted = Status::INDEX_NEW
+        | Status::INDEX_MODIFIED
+        | Status::INDEX_DELETED
+        | Status::INDEX_RENAMED
+        | Status::INDEX_TYPECHANGE
+        | Status::WT_NEW
+        | Status::WT_MODIFIED
+        | Status::WT_DELETED
+        | Status::WT_TYPECHANGE
+        | Status::WT_RENAMED
+        | Status::CONFLICTED;
+    statuses.iter().all(|s| !s.status().intersects(uncommitted))
 }
 
 #[allow(unused)]
@@ -111,7 +114,7 @@ pub fn do_fetch<'a>(
     }
 
     let fetch_head = repo.find_reference("FETCH_HEAD")?;
-    Ok(repo.reference_to_annotated_commit(&fetch_head)?)
+    repo.reference_to_annotated_commit(&fetch_head)
 }
 
 pub fn fast_forward(
@@ -182,7 +185,7 @@ pub fn checkout_local_branch(repo: &Repository, branch_name: &str) -> Result<(),
         }
     };
 
-    if !is_working_tree_clean(&repo) {
+    if !is_working_tree_clean(repo) {
         return Err(git2::Error::from_str(
             "Workspace is not clean. Please commit or stash your changes.",
         ));
@@ -220,12 +223,14 @@ pub fn stage_and_commit_all_changes(
     );
     let mut index = repo.index()?;
     index.add_all(["."].iter(), IndexAddOption::DEFAULT, None)?;
+    // Also stage deleted files, which `add_all` leaves in the index
+    index.update_all(["."].iter(), None)?;
     index.write()?;
 
     let tree_id = index.write_tree()?;
     let tree = repo.find_tree(tree_id)?;
     let signature = repo.signature()?;
-    let parent_commit = find_last_commit_on_branch(&repo, branch_name)?;
+    let parent_commit = find_last_commit_on_branch(repo, branch_name)?;
     let new_commit_iod = repo.commit(
         Some("HEAD"),
         &signature,
@@ -244,6 +249,39 @@ pub fn stage_and_commit_all_changes(
     Ok(())
 }
 
+/// Moves a branch from commit `from` to `to`, refusing if it's no longer at `from`. If the branch
+/// is checked out in the repository, its checkout is moved along with it, keeping uncommitted
+/// changes to other files, and refusing if they touch a file which changes.
+pub fn move_branch(
+    repo: &Repository,
+    branch_name: &str,
+    from: Oid,
+    to: Oid,
+) -> Result<(), git2::Error> {
+    let refname = format!("refs/heads/{}", branch_name);
+    if repo.refname_to_id(&refname)? != from {
+        return Err(git2::Error::from_str(&format!(
+            "Branch {} moved while it was being committed to",
+            branch_name
+        )));
+    }
+    let checked_out = repo
+        .head()
+        .is_ok_and(|head| head.name() == Some(refname.as_str()));
+    if checked_out {
+        let tree = repo.find_commit(to)?.tree()?;
+        repo.checkout_tree(tree.as_object(), Some(CheckoutBuilder::new().safe()))?;
+    }
+    repo.reference_matching(
+        &refname,
+        to,
+        true,
+        from,
+        "workspace-version-tools: move branch",
+    )?;
+    Ok(())
+}
+
 fn find_last_commit_on_branch<'a>(
     repo: &'a Repository,
     branch_name: &'a str,
@@ -259,7 +297,7 @@ fn find_last_commit_on_branch<'a>(
                 Err(e) => Err(e),
             }
         }
-        Err(_) => return Err(git2::Error::from_str("branch doesn't exist!")),
+        Err(_) => Err(git2::Error::from_str("branch doesn't exist!")),
     }
 }
 
@@ -307,6 +345,27 @@ pub fn create_and_checkout_branch(
         }
     };
 
+    let refname = create_branch(repo, remote_name, branch_name, &commit)?;
+
+    // Set the HEAD to point to the new branch
+    repo.set_head(&refname)?;
+
+    // Checkout the new branch to update the working directory
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+
+    log::info!("✅ Created and checked out new branch {}", branch_name);
+
+    Ok(())
+}
+
+/// Creates a new branch pointing at `commit`, replacing any existing branch of the same name both
+/// locally and on the remote. Returns the branch's canonical name (e.g. "refs/heads/new_branch").
+pub fn create_branch(
+    repo: &Repository,
+    remote_name: &str,
+    branch_name: &str,
+    commit: &Commit,
+) -> Result<String, git2::Error> {
     // if the branch already exists delete it
     if let Ok(mut branch) = repo.find_branch(branch_name, git2::BranchType::Local) {
         log::warn!(
@@ -319,33 +378,104 @@ pub fn create_and_checkout_branch(
     // if the branch exists on the remote and delete it
     let mut remote = repo.find_remote(remote_name)?;
     let refspec = format!(":refs/heads/{}", branch_name); // : is refspec for deletion
-    match remote.push(&[&refspec], Some(&mut PushOptions::new())) {
-        Ok(_) => log::info!(
+    if remote
+        .push(&[&refspec], Some(&mut PushOptions::new()))
+        .is_ok()
+    {
+        log::info!(
             "Branch {} already exists on remote, deleting it.",
             branch_name
-        ),
-        Err(_) => {}
+        )
     };
 
-    // Create a new branch pointing to the current HEAD commit
-    let branch = repo.branch(branch_name, &commit, false)?;
-
-    // Get the branch's canonical name (e.g. "refs/heads/new_branch")
+    let branch = repo.branch(branch_name, commit, false)?;
     let refname = branch
         .into_reference()
         .name()
         .expect("Branch name not found")
         .to_string();
 
-    // Set the HEAD to point to the new branch
-    repo.set_head(&refname)?;
+    Ok(refname)
+}
 
-    // Checkout the new branch to update the working directory
-    repo.checkout_head(Some(git2::build::CheckoutBuilder::default().force()))?;
+/// Whether a tag named `name` exists.
+pub fn tag_exists(repo: &Repository, name: &str) -> bool {
+    repo.find_reference(&format!("refs/tags/{}", name)).is_ok()
+}
 
-    log::info!("✅ Created and checked out new branch {}", branch_name);
+/// Creates an annotated tag on the head of a branch, without overwriting any existing tag.
+pub fn create_annotated_tag(
+    repo: &Repository,
+    name: &str,
+    branch_name: &str,
+    message: &str,
+) -> Result<Oid, git2::Error> {
+    let target = repo.revparse_single(&format!("refs/heads/{}", branch_name))?;
+    let signature = repo.signature()?;
+    let id = repo.tag(name, &target, &signature, message, false)?;
+    log::info!("🏷️ Created tag {} on branch {}", name, branch_name);
+    Ok(id)
+}
 
-    Ok(())
+/// Paths, relative to the repository root, of the files which differ between the tree of `since`
+/// and the working tree, including staged and untracked files.
+pub fn changed_files(repo: &Repository, since: &str) -> Result<Vec<PathBuf>, git2::Error> {
+    let tree = repo.revparse_single(since)?.peel_to_tree()?;
+    let mut options = DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repo.diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))?;
+    Ok(diff_paths(&diff))
+}
+
+/// Paths of the files changed by a diff.
+fn diff_paths(diff: &Diff) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for delta in diff.deltas() {
+        // Renamed files changed both their old and new locations
+        for file in [delta.old_file(), delta.new_file()] {
+            if let Some(path) = file.path() {
+                if !files.iter().any(|f: &PathBuf| f == path) {
+                    files.push(path.to_owned());
+                }
+            }
+        }
+    }
+    files
+}
+
+/// A commit along with the files it changed.
+pub struct CommitChanges {
+    pub id: Oid,
+    pub message: String,
+    /// Paths relative to the repository root of the files changed compared to the first parent
+    pub files: Vec<PathBuf>,
+}
+
+/// The commits reachable from `head` but not from `since`, newest first.
+pub fn commits_since(
+    repo: &Repository,
+    since: &str,
+    head: &str,
+) -> Result<Vec<CommitChanges>, git2::Error> {
+    let mut revwalk = repo.revwalk()?;
+    revwalk.push(repo.revparse_single(head)?.peel_to_commit()?.id())?;
+    revwalk.hide(repo.revparse_single(since)?.peel_to_commit()?.id())?;
+
+    let mut commits = vec![];
+    for id in revwalk {
+        let commit = repo.find_commit(id?)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        commits.push(CommitChanges {
+            id: commit.id(),
+            message: String::from_utf8_lossy(commit.message_bytes()).into_owned(),
+            files: diff_paths(&diff),
+        });
+    }
+    Ok(commits)
 }
 
 fn reset_cargo_lock(repo: &Repository) -> Result<(), Box<dyn std::error::Error>> {
@@ -365,3 +495,23 @@ fn reset_cargo_lock(repo: &Repository) -> Result<(), Box<dyn std::error::Error>>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::mocks::init_mock_repository;
+    use std::fs;
+
+    #[test]
+    fn staged_changes_make_the_working_tree_dirty() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        assert!(is_working_tree_clean(&repo));
+
+        fs::write(path.join("x/Cargo.toml"), "staged").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("x/Cargo.toml")).unwrap();
+        index.write().unwrap();
+        assert!(!is_working_tree_clean(&repo));
+    }
+}
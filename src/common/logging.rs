@@ -1,3 +1,5 @@
+use std::fmt::{self, Display};
+
 pub const RED: &str = "\x1b[31m";
 pub const GREEN: &str = "\x1b[32m";
 pub const BLUE: &str = "\x1b[34m";
@@ -9,12 +11,12 @@ pub enum Color {
     Blue,
 }
 
-impl ToString for Color {
-    fn to_string(&self) -> String {
+impl Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            // Color::Red => RED.to_string(),
-            Color::Green => GREEN.to_string(),
-            Color::Blue => BLUE.to_string(),
+            // Color::Red => write!(f, "{}", RED),
+            Color::Green => write!(f, "{}", GREEN),
+            Color::Blue => write!(f, "{}", BLUE),
         }
     }
 }
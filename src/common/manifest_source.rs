@@ -0,0 +1,245 @@
+use git2::{ObjectType, Repository, Tree};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+/// Where the files of a workspace are read from, with paths relative to the workspace root.
+pub trait ManifestSource {
+    /// Contents of the file at `path`, or None if there is no such file.
+    fn read(&self, path: &Path) -> Result<Option<String>, String>;
+    /// Names of the directories in the directory at `path`.
+    fn directories(&self, path: &Path) -> Result<Vec<String>, String>;
+    /// Names of the files in the directory at `path`.
+    fn files(&self, path: &Path) -> Result<Vec<String>, String>;
+}
+
+/// The files checked out in the working tree.
+pub struct WorkingTree {
+    pub path: PathBuf,
+}
+
+impl ManifestSource for WorkingTree {
+    fn read(&self, path: &Path) -> Result<Option<String>, String> {
+        let path = self.path.join(path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        fs::read_to_string(&path)
+            .map(Some)
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))
+    }
+
+    fn directories(&self, path: &Path) -> Result<Vec<String>, String> {
+        let path = self.path.join(path);
+        if !path.is_dir() {
+            return Ok(vec![]);
+        }
+        let entries =
+            fs::read_dir(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let mut directories = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            if entry.path().is_dir() {
+                directories.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(directories)
+    }
+
+    fn files(&self, path: &Path) -> Result<Vec<String>, String> {
+        let path = self.path.join(path);
+        if !path.is_dir() {
+            return Ok(vec![]);
+        }
+        let entries =
+            fs::read_dir(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        let mut files = vec![];
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            if entry.path().is_file() {
+                files.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        Ok(files)
+    }
+}
+
+/// The files committed to a git tree, e.g. the head of a branch which isn't checked out.
+pub struct GitTree<'r> {
+    repo: &'r Repository,
+    tree: Tree<'r>,
+}
+
+impl<'r> GitTree<'r> {
+    /// The tree of the commit `reference` resolves to, e.g. `stable` or `origin/stable`.
+    pub fn new(repo: &'r Repository, reference: &str) -> Result<Self, String> {
+        let tree = repo
+            .revparse_single(reference)
+            .and_then(|o| o.peel_to_tree())
+            .map_err(|e| format!("Failed to find the tree of {}: {}", reference, e))?;
+        Ok(Self { repo, tree })
+    }
+
+    /// Names of the entries of a kind in the directory at `path`.
+    fn entries(&self, path: &Path, kind: ObjectType) -> Result<Vec<String>, String> {
+        let tree = if path.as_os_str().is_empty() {
+            self.tree.clone()
+        } else {
+            match self.tree.get_path(path) {
+                Ok(entry) if entry.kind() == Some(ObjectType::Tree) => self
+                    .repo
+                    .find_tree(entry.id())
+                    .map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+                _ => return Ok(vec![]),
+            }
+        };
+        Ok(tree
+            .iter()
+            .filter(|e| e.kind() == Some(kind))
+            .filter_map(|e| e.name().map(|n| n.to_owned()))
+            .collect())
+    }
+}
+
+impl ManifestSource for GitTree<'_> {
+    fn read(&self, path: &Path) -> Result<Option<String>, String> {
+        let Ok(entry) = self.tree.get_path(path) else {
+            return Ok(None);
+        };
+        if entry.kind() != Some(ObjectType::Blob) {
+            return Ok(None);
+        }
+        let blob = self
+            .repo
+            .find_blob(entry.id())
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        String::from_utf8(blob.content().to_vec())
+            .map(Some)
+            .map_err(|e| format!("{:?} is not valid UTF-8: {}", path, e))
+    }
+
+    fn directories(&self, path: &Path) -> Result<Vec<String>, String> {
+        self.entries(path, ObjectType::Tree)
+    }
+
+    fn files(&self, path: &Path) -> Result<Vec<String>, String> {
+        self.entries(path, ObjectType::Blob)
+    }
+}
+
+/// Expands the `[workspace] members` patterns, which may use `*` and `?` wildcards within a path
+/// segment, into the directories of the members, leaving out those in or under a directory
+/// matching an `exclude` pattern. Exclude patterns may also use `**` for any number of segments.
+pub fn expand_members(
+    source: &dyn ManifestSource,
+    members: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, String> {
+    let mut expanded = vec![];
+    for pattern in members {
+        let mut candidates = vec![PathBuf::new()];
+        for segment in Path::new(pattern).iter() {
+            let segment = segment.to_string_lossy();
+            let mut next = vec![];
+            for candidate in candidates {
+                if !segment.contains(['*', '?']) {
+                    next.push(candidate.join(segment.as_ref()));
+                    continue;
+                }
+                let mut matches = source
+                    .directories(&candidate)?
+                    .into_iter()
+                    .filter(|d| matches_pattern(&segment, d))
+                    .collect::<Vec<_>>();
+                matches.sort();
+                next.extend(matches.into_iter().map(|d| candidate.join(d)));
+            }
+            candidates = next;
+        }
+
+        for candidate in candidates {
+            let excluded = exclude.iter().any(|e| is_excluded(e, &candidate));
+            let is_package = source.read(&candidate.join("Cargo.toml"))?.is_some();
+            if !excluded && is_package && !expanded.contains(&candidate) {
+                expanded.push(candidate);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
+/// Whether `path` or one of its ancestors matches the exclude pattern, like cargo excluding a
+/// directory along with everything under it.
+fn is_excluded(pattern: &str, path: &Path) -> bool {
+    let segments = |path: &Path| {
+        path.components()
+            .filter(|c| *c != Component::CurDir)
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+    };
+    let (pattern, path) = (segments(Path::new(pattern)), segments(path));
+    (1..=path.len()).any(|len| matches_segments(&pattern, &path[..len]))
+}
+
+/// Whether the path segments match the pattern's, where a `**` segment matches any number of
+/// segments and the others are matched by `matches_pattern`.
+fn matches_segments(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((segment, rest)) if segment == "**" => {
+            (0..=path.len()).any(|skipped| matches_segments(rest, &path[skipped..]))
+        }
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path)| {
+            matches_pattern(segment, name) && matches_segments(rest, path)
+        }),
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters and `?` any single
+/// character.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` seen, and of the name when it was seen, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[test]
+fn pattern_matching() {
+    assert!(matches_pattern("*", "pallet-balances"));
+    assert!(matches_pattern("pallet-*", "pallet-balances"));
+    assert!(matches_pattern("*-balances", "pallet-balances"));
+    assert!(matches_pattern("p?llet-*s", "pallet-balances"));
+    assert!(!matches_pattern("pallet-*", "frame-support"));
+    assert!(!matches_pattern("pallet-?", "pallet-balances"));
+}
+
+#[test]
+fn exclude_matching() {
+    assert!(is_excluded("examples", Path::new("examples")));
+    assert!(is_excluded("./examples/", Path::new("examples/hello")));
+    assert!(is_excluded("examples/*", Path::new("examples/hello")));
+    assert!(is_excluded("**/fuzz", Path::new("pallets/balances/fuzz")));
+    assert!(is_excluded("pallets/**/fuzz", Path::new("pallets/fuzz")));
+    assert!(is_excluded("pallet-?", Path::new("pallet-a")));
+    assert!(!is_excluded("examples/*", Path::new("examples")));
+    assert!(!is_excluded("**/fuzz", Path::new("pallets/fuzzer")));
+    assert!(!is_excluded("pallets/**/fuzz", Path::new("frame/fuzz")));
+}
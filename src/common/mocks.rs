@@ -0,0 +1,60 @@
+//! Helpers for tests working with the mock workspaces in the repository `mocks` directory.
+
+use crate::common::workspace::Workspace;
+use fs_extra::dir::{self, CopyOptions};
+use git2::{IndexAddOption, Repository};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+/// Create a copy of a mock workspace which can be safely modified in tests.
+pub(crate) fn copy_mock_workspace(name: &str) -> PathBuf {
+    let mocks_root = Path::new(env!("CARGO_MANIFEST_DIR")).join("mocks");
+
+    let temp_dir_path = TempDir::new("workspace-version-tools-test")
+        .unwrap()
+        .into_path();
+    let mut options = CopyOptions::new();
+    options.overwrite = true;
+    dir::copy(mocks_root.join(name), &temp_dir_path, &options).expect("failed to copy workspace");
+
+    temp_dir_path.join(name)
+}
+
+/// Create copies of the stable and prerelease mock workspaces which can be safely modified in
+/// tests.
+pub(crate) fn get_mock_workspaces() -> (Workspace, Workspace) {
+    (
+        Workspace::new_test_workspace(copy_mock_workspace("stable_workspace")).unwrap(),
+        Workspace::new_test_workspace(copy_mock_workspace("prerelease_workspace")).unwrap(),
+    )
+}
+
+/// Create a copy of a mock workspace in a new git repository, with everything committed to a
+/// `main` branch.
+pub(crate) fn init_mock_repository(name: &str) -> PathBuf {
+    let path = copy_mock_workspace(name);
+    let repo = Repository::init(&path).unwrap();
+    let mut config = repo.config().unwrap();
+    config.set_str("user.name", "test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["."].iter(), IndexAddOption::DEFAULT, None)
+        .unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = repo.signature().unwrap();
+    repo.commit(
+        Some("refs/heads/main"),
+        &signature,
+        &signature,
+        "initial",
+        &tree,
+        &[],
+    )
+    .unwrap();
+    repo.set_head("refs/heads/main").unwrap();
+
+    path
+}
@@ -1,6 +1,19 @@
 pub mod bump_tree;
+pub mod changelog;
+pub mod changeset;
+pub mod conventional_commits;
+pub mod diff;
 pub mod git;
 pub mod logging;
+pub mod manifest_source;
+#[cfg(test)]
+pub(crate) mod mocks;
+pub mod output;
 pub mod package;
+pub mod plan;
+pub mod registry;
+pub mod root_manifest;
+pub mod transaction;
 pub mod version_extension;
 pub mod workspace;
+pub mod worktree;
@@ -0,0 +1,47 @@
+use serde::Serialize;
+
+/// How command results are printed to stdout. Logs always go to stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable trees and diffs
+    Text,
+    /// A single JSON document, for tools consuming the results
+    Json,
+    /// The bump tree as a Graphviz DOT graph, for `bump --dry-run`
+    Dot,
+    /// The bump tree as a Mermaid flowchart, for `bump --dry-run`
+    Mermaid,
+    /// Markdown release notes listing every bump, for `bump`
+    ReleaseNotes,
+}
+
+impl OutputFormat {
+    pub fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Invalid output format: {}", s)),
+        }
+    }
+}
+
+/// Prints `value` as pretty JSON with its object keys sorted, so the output is deterministic.
+pub fn print_json(value: &impl Serialize) {
+    // serde_json's maps are sorted by key, so round-tripping through a Value sorts struct fields too
+    let value = serde_json::to_value(value).expect("command output is always serializable");
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&value).expect("JSON values are always serializable")
+    );
+}
+
+#[test]
+fn sorts_keys() {
+    #[derive(Serialize)]
+    struct Unsorted {
+        b: u8,
+        a: u8,
+    }
+    let value = serde_json::to_value(Unsorted { b: 1, a: 2 }).unwrap();
+    assert_eq!(value.to_string(), r#"{"a":2,"b":1}"#);
+}
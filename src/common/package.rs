@@ -1,28 +1,37 @@
-use cargo_metadata::DependencyKind;
-use crates_io_api::AsyncClient;
-use semver::Version;
+use super::diff::unified_diff;
+use super::registry::CRATES_IO;
+use super::root_manifest::{is_inherited, RootManifest, METADATA_KEY};
+use semver::{Comparator, Op, Version, VersionReq};
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
     fmt::Display,
     fs,
     hash::{Hash, Hasher},
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
 };
-use toml_edit::{Document, Table};
+use toml_edit::{Document, Item, Table, TableLike, Value};
+
+/// Names of the manifest tables which declare dependencies. They may appear at the top level or
+/// nested under `[target.'cfg(..)']`.
+const DEPENDENCY_TABLES: [&str; 3] = ["dependencies", "build-dependencies", "dev-dependencies"];
 
 /// A wrapper around the toml_edit Document with convenience methods
 #[derive(Debug)]
 pub struct Package {
-    /// The doc
+    /// The doc, including any edits not yet written to disk
     doc: Document,
+    /// Contents of the manifest on disk
+    original: String,
     /// Path
     path: PathBuf,
     /// Direct, non-development dependencies that are also workspace members
     direct_workspace_dependencies: HashSet<String>,
     /// Direct, non-development dependents that are also workspace members
     direct_workspace_dependents: Option<HashMap<String, Rc<RefCell<Package>>>>,
+    /// Root manifest of the workspace, which inherited values are read from
+    root: Rc<RefCell<RootManifest>>,
     /// Branch name
     pub branch: String,
 }
@@ -49,37 +58,53 @@ impl Display for Package {
 }
 
 impl Package {
-    fn package(self: &Self) -> &Table {
+    fn package(&self) -> &Table {
         self.doc
             .get("package")
             .and_then(|p| p.as_table())
-            .expect(format!("Package {:?} is missing [package] table", self.path).as_str())
+            .unwrap_or_else(|| panic!("Package {:?} is missing [package] table", self.path))
     }
 
-    fn package_mut(self: &mut Self) -> &mut Table {
+    fn package_mut(&mut self) -> &mut Table {
         self.doc
             .get_mut("package")
             .and_then(|p| p.as_table_mut())
-            .expect(format!("Package {:?} is missing [package] table", self.path).as_str())
+            .unwrap_or_else(|| panic!("Package {:?} is missing [package] table", self.path))
     }
 
-    pub fn name(self: &Self) -> String {
+    pub fn name(&self) -> String {
         self.package()
             .get("name")
             .and_then(|n| n.as_str())
-            .expect(format!("Package {:?} has invalid name", self.path).as_str())
+            .unwrap_or_else(|| panic!("Package {:?} has invalid name", self.path))
             .to_owned()
     }
 
-    pub fn version(self: &Self) -> Version {
+    pub fn version(&self) -> Version {
+        if self.inherits_version() {
+            return self.root.borrow().package_version().unwrap_or_else(|| {
+                panic!(
+                    "Package {:?} inherits its version, but the workspace doesn't set one",
+                    self.path
+                )
+            });
+        }
+
         let version_str = self
             .package()
             .get("version")
             .and_then(|v| v.as_str())
-            .expect(format!("Package {:?} has invalid version", self.path).as_str());
+            .unwrap_or_else(|| panic!("Package {:?} has invalid version", self.path));
 
         Version::parse(version_str)
-            .expect(format!("Failed to create Version from {:?} version", self.path).as_str())
+            .unwrap_or_else(|_| panic!("Failed to create Version from {:?} version", self.path))
+    }
+
+    /// Directory holding the package's manifest.
+    pub fn directory(&self) -> &Path {
+        self.path
+            .parent()
+            .unwrap_or_else(|| panic!("Manifest {:?} has no parent directory", self.path))
     }
 
     pub fn direct_workspace_dependents(&self) -> impl Iterator<Item = Rc<RefCell<Package>>> {
@@ -99,72 +124,154 @@ impl Package {
         &self.direct_workspace_dependencies
     }
 
+    /// Drops the dependencies which aren't on members of the workspace.
+    pub fn retain_workspace_dependencies(&mut self, workspace_members: &HashSet<String>) {
+        self.direct_workspace_dependencies
+            .retain(|d| workspace_members.contains(d));
+    }
+
     pub fn set_direct_dependents(
-        self: &mut Self,
+        &mut self,
         direct_dependents: HashMap<String, Rc<RefCell<Package>>>,
     ) {
         self.direct_workspace_dependents = Some(direct_dependents);
     }
 
-    pub fn set_version(self: &mut Self, version: &Version) {
+    /// Whether the package uses `version.workspace = true`.
+    pub fn inherits_version(&self) -> bool {
+        self.package().get("version").is_some_and(is_inherited)
+    }
+
+    /// Sets the version in the package manifest. Packages inheriting their version from the
+    /// workspace stop doing so.
+    pub fn set_version(&mut self, version: &Version) {
         log::debug!("Bumping {} to {}", self.name(), version);
 
+        if self.inherits_version() {
+            log::warn!(
+                "⚠️ {} inherits its version from the workspace, but is being set to {} on its own. Replacing `version.workspace = true` with an explicit version.",
+                self.name(),
+                version
+            );
+            // Dotted keys have no whitespace before the `.`, which would otherwise be kept
+            // before the `=`.
+            if let Some(decor) = self.package_mut().key_decor_mut("version") {
+                decor.set_suffix(" ");
+            }
+        }
+
         self.package_mut()["version"] = toml_edit::value(version.to_string());
-        fs::write(self.path.clone(), self.doc.to_string())
-            .expect(format!("Failed to write to {:?}", self.path).as_str())
     }
 
-    pub async fn crates_io_version(self: &Self, client: &AsyncClient) -> Result<Version, String> {
-        let crates_io_version_str = client
-            .get_crate(self.name().as_str())
-            .await
-            .map_err(|e| format!("Failed to get crate from crates.io: {}", e))?
-            .crate_data
-            .max_version;
+    /// Updates every declaration of `dependency` in the manifest to require `version`.
+    pub fn set_dependency_version(&mut self, dependency: &str, version: &Version) {
+        if set_dependency_version_req(&mut self.doc, dependency, version) {
+            log::debug!(
+                "Updated {} requirement on {} to {}",
+                self.name(),
+                dependency,
+                version
+            );
+        }
+    }
 
-        Ok(Version::parse(crates_io_version_str.as_str())
-            .expect(format!("crates.io returned bad version for crate {}", self.name()).as_str()))
+    /// Writes any edits made to the manifest to disk.
+    pub fn write(&mut self) {
+        let content = self.doc.to_string();
+        if content != self.original {
+            fs::write(self.path.clone(), &content)
+                .unwrap_or_else(|_| panic!("Failed to write to {:?}", self.path));
+            self.original = content;
+        }
     }
 
-    pub fn publish(self: &Self) -> bool {
-        if let Some(publish) = self.package().get("publish").and_then(|p| p.as_bool()) {
-            if !publish {
-                return false;
+    /// Moves the manifest from the checkout of the repository at `from` to the one at `to`.
+    pub fn relocate(&mut self, from: &Path, to: &Path) {
+        self.path = to.join(
+            self.path
+                .strip_prefix(from)
+                .unwrap_or_else(|_| panic!("Manifest {:?} is outside of {:?}", self.path, from)),
+        );
+    }
+
+    /// A unified diff of the edits not yet written to disk, with paths relative to `base`.
+    pub fn diff(&self, base: &Path) -> Option<String> {
+        unified_diff(
+            self.path.strip_prefix(base).unwrap_or(&self.path),
+            &self.original,
+            &self.doc.to_string(),
+        )
+    }
+
+    /// The version group declared in `[package.metadata.workspace-version-tools] version-group`.
+    pub fn version_group(&self) -> Result<Option<String>, String> {
+        self.package()
+            .get("metadata")
+            .and_then(|m| m.get(METADATA_KEY))
+            .and_then(|t| t.get("version-group"))
+            .map(|g| {
+                g.as_str()
+                    .map(|g| g.to_owned())
+                    .ok_or_else(|| format!("Package {:?} has invalid version-group", self.path))
+            })
+            .transpose()
+    }
+
+    /// Names of the registries the package may be published to, from its `publish` field, with
+    /// crates.io named `crates-io`. Empty for `publish = false`.
+    pub fn publish_registries(&self) -> Vec<String> {
+        match self.package().get("publish") {
+            Some(publish) if is_inherited(publish) => {
+                let root = self.root.borrow();
+                let publish = root.package_publish().unwrap_or_else(|| {
+                    panic!(
+                        "Package {:?} inherits publish, but the workspace doesn't set it",
+                        self.path
+                    )
+                });
+                registries_allowed_by(publish, &self.path)
             }
+            Some(publish) => registries_allowed_by(publish, &self.path),
+            None => vec![CRATES_IO.to_owned()],
         }
-        return true;
     }
 
+    /// Loads the package from the contents of its manifest at `path`. Its dependencies must then
+    /// be narrowed down to workspace members with `retain_workspace_dependencies`.
     pub fn new(
-        cargo_metadata_package: &cargo_metadata::Package,
-        workspace_members: &HashSet<String>,
+        path: PathBuf,
+        content: String,
+        root: &Rc<RefCell<RootManifest>>,
         branch: &str,
     ) -> Result<Self, String> {
-        let path = cargo_metadata_package.manifest_path.clone();
-        let content = fs::read_to_string(&path).map_err(|e| {
-            format!(
-                "Failed to read Cargo.toml for package at path {:?}: {}",
-                path, e
-            )
-        })?;
         let doc = content
             .parse::<Document>()
             .map_err(|e| format!("Package Cargo.toml at path {:?} is invalid: {}", path, e))?;
+        doc.get("package")
+            .and_then(|p| p.get("name"))
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| format!("Package Cargo.toml at path {:?} has no name", path))?;
+
+        // Dependencies may be renamed, or declared through their `[workspace.dependencies]` entry
+        // which may itself be renamed
+        let direct_workspace_dependencies = dependency_tables(&doc)
+            .into_iter()
+            .filter(|(kind, _)| *kind != "dev-dependencies")
+            .flat_map(|(_, table)| table.iter())
+            .map(|(key, dep)| match is_inherited(dep) {
+                true => root.borrow().workspace_dependency_package_name(key),
+                false => dependency_package_name(key, dep).to_owned(),
+            })
+            .collect::<HashSet<_>>();
 
         Ok(Self {
             doc,
+            original: content,
             branch: branch.to_owned(),
             direct_workspace_dependents: None,
-            direct_workspace_dependencies: cargo_metadata_package
-                .dependencies
-                .iter()
-                .filter(|d| {
-                    workspace_members.contains(d.name.as_str())
-                        && d.kind != DependencyKind::Development
-                })
-                .map(|d| d.name.clone())
-                .collect(),
-            path: path.into(),
+            root: root.clone(),
+            direct_workspace_dependencies,
+            path,
         })
     }
 }
@@ -183,6 +290,163 @@ pub fn find_direct_dependents(
     dependents
 }
 
+/// All dependency tables in a manifest along with their names, including platform specific ones.
+fn dependency_tables(doc: &Document) -> Vec<(&str, &dyn TableLike)> {
+    let mut tables = vec![];
+    for (key, item) in doc.iter() {
+        if DEPENDENCY_TABLES.contains(&key) {
+            tables.extend(item.as_table_like().map(|t| (key, t)));
+        } else if key == "target" {
+            for (_, platform) in item.as_table_like().into_iter().flat_map(|t| t.iter()) {
+                for (key, item) in platform.as_table_like().into_iter().flat_map(|t| t.iter()) {
+                    if DEPENDENCY_TABLES.contains(&key) {
+                        tables.extend(item.as_table_like().map(|t| (key, t)));
+                    }
+                }
+            }
+        }
+    }
+    tables
+}
+
+/// All dependency tables in a manifest, including platform specific ones.
+fn dependency_tables_mut(doc: &mut Document) -> Vec<&mut dyn TableLike> {
+    let mut tables = vec![];
+    for (key, item) in doc.as_table_mut().iter_mut() {
+        if DEPENDENCY_TABLES.contains(&key.get()) {
+            tables.extend(item.as_table_like_mut());
+        } else if key.get() == "target" {
+            for (_, platform) in item
+                .as_table_like_mut()
+                .into_iter()
+                .flat_map(|t| t.iter_mut())
+            {
+                for (key, item) in platform
+                    .as_table_like_mut()
+                    .into_iter()
+                    .flat_map(|t| t.iter_mut())
+                {
+                    if DEPENDENCY_TABLES.contains(&key.get()) {
+                        tables.extend(item.as_table_like_mut());
+                    }
+                }
+            }
+        }
+    }
+    tables
+}
+
+/// Rewrites the version requirement of every `dependency` entry in the manifest which declares
+/// one, so that it requires `version`.
+///
+/// Returns whether the manifest was changed.
+fn set_dependency_version_req(doc: &mut Document, dependency: &str, version: &Version) -> bool {
+    let mut changed = false;
+    for table in dependency_tables_mut(doc) {
+        changed |= set_table_dependency_version_req(table, dependency, version);
+    }
+    changed
+}
+
+/// Rewrites the version requirement of every entry in a dependency table on the package
+/// `dependency` which declares one, so that it requires `version`. Entries are matched by package
+/// name, so renamed dependencies (`key = { package = "..." }`) are rewritten under their own key.
+/// The requirement operator (`=`, `^`, `~`) is kept. Other requirements, e.g. compound ones like
+/// `">=0.1, <0.2"`, can't be rewritten without changing their meaning, so they're left alone with
+/// a warning.
+///
+/// Returns whether the table was changed.
+pub fn set_table_dependency_version_req(
+    table: &mut dyn TableLike,
+    dependency: &str,
+    version: &Version,
+) -> bool {
+    let mut changed = false;
+    for (key, dep) in table.iter_mut() {
+        if dependency_package_name(key.get(), dep) != dependency {
+            continue;
+        }
+        let Some(req_item) = dep.as_table_like_mut().and_then(|d| d.get_mut("version")) else {
+            continue;
+        };
+        let Some(cur_req) = req_item.as_str() else {
+            continue;
+        };
+        if !is_single_version_req(cur_req) {
+            log::warn!(
+                "⚠️ Not rewriting {} requirement {:?} on {} to {}, only =, ^ and ~ requirements on a single version are",
+                key.get(),
+                cur_req,
+                dependency,
+                version
+            );
+            continue;
+        }
+
+        let operator = cur_req
+            .trim_start()
+            .chars()
+            .take_while(|c| matches!(c, '=' | '^' | '~'))
+            .collect::<String>();
+        let next_req = format!("{}{}", operator, version);
+        if cur_req != next_req {
+            set_str_keeping_decor(req_item, &next_req);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Whether a requirement is on a single version with the `=`, `^` or `~` operator, or none.
+fn is_single_version_req(req: &str) -> bool {
+    VersionReq::parse(req).is_ok_and(|req| {
+        matches!(
+            req.comparators.as_slice(),
+            [Comparator {
+                op: Op::Exact | Op::Caret | Op::Tilde,
+                ..
+            }]
+        )
+    })
+}
+
+/// The name of the package a dependency entry refers to. This is its key, unless the dependency
+/// is renamed with `package = "..."`.
+pub fn dependency_package_name<'a>(key: &'a str, dep: &'a Item) -> &'a str {
+    dep.get("package").and_then(|p| p.as_str()).unwrap_or(key)
+}
+
+/// Replaces a string value without disturbing the whitespace and comments around it.
+pub fn set_str_keeping_decor(item: &mut Item, s: &str) {
+    match item.as_value_mut() {
+        Some(value) => {
+            let decor = value.decor().clone();
+            *value = Value::from(s);
+            *value.decor_mut() = decor;
+        }
+        None => *item = toml_edit::value(s),
+    }
+}
+
+/// The registries a `publish` field allows: all of those listed, or crates.io if it's `true`.
+fn registries_allowed_by(publish: &Item, path: &Path) -> Vec<String> {
+    if let Some(publish) = publish.as_bool() {
+        return match publish {
+            true => vec![CRATES_IO.to_owned()],
+            false => vec![],
+        };
+    }
+    publish
+        .as_array()
+        .and_then(|registries| {
+            registries
+                .iter()
+                .map(|r| r.as_str().map(|r| r.to_owned()))
+                .collect::<Option<Vec<_>>>()
+        })
+        .unwrap_or_else(|| panic!("Package {:?} has invalid publish", path))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -221,4 +485,108 @@ mod tests {
         let direct_dependents_a = find_direct_dependents("package_a", &workspace_deps);
         assert!(direct_dependents_a.is_empty());
     }
+
+    #[test]
+    fn test_set_renamed_dependency_version_req() {
+        let mut doc = r#"
+[dependencies]
+package_b = { path = "../b", version = "0.1.0" }
+renamed_b = { package = "package_b", path = "../b", version = "0.1.0" }
+renamed_c = { package = "package_c", path = "../c", version = "0.1.0" }
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let version = Version::parse("0.2.0").unwrap();
+        assert!(set_dependency_version_req(&mut doc, "package_b", &version));
+        assert!(!set_dependency_version_req(&mut doc, "renamed_c", &version));
+        assert_eq!(
+            doc.to_string(),
+            r#"
+[dependencies]
+package_b = { path = "../b", version = "0.2.0" }
+renamed_b = { package = "package_b", path = "../b", version = "0.2.0" }
+renamed_c = { package = "package_c", path = "../c", version = "0.1.0" }
+"#
+        );
+    }
+
+    #[test]
+    fn test_set_dependency_version_req() {
+        let mut doc = r#"
+[package]
+name = "package_a"
+version = "0.1.0"
+
+[dependencies]
+package_b = { path = "../b", version = "0.1" } # keep me
+package_c = { path = "../c" }
+
+[build-dependencies.package_b]
+path = "../b"
+version = "=0.1.0"
+
+[dev-dependencies]
+package_b = { path = "../b", version = "~0.1.0" }
+
+[target.'cfg(unix)'.dependencies]
+package_b = { version = "^0.1.0", path = "../b" }
+
+[target.'cfg(windows)'.dependencies]
+package_b = { path = "../b", version = ">=0.1, <0.2" }
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let version = Version::parse("0.2.0").unwrap();
+        assert!(set_dependency_version_req(&mut doc, "package_b", &version));
+        assert_eq!(
+            doc.to_string(),
+            r#"
+[package]
+name = "package_a"
+version = "0.1.0"
+
+[dependencies]
+package_b = { path = "../b", version = "0.2.0" } # keep me
+package_c = { path = "../c" }
+
+[build-dependencies.package_b]
+path = "../b"
+version = "=0.2.0"
+
+[dev-dependencies]
+package_b = { path = "../b", version = "~0.2.0" }
+
+[target.'cfg(unix)'.dependencies]
+package_b = { version = "^0.2.0", path = "../b" }
+
+[target.'cfg(windows)'.dependencies]
+package_b = { path = "../b", version = ">=0.1, <0.2" }
+"#
+        );
+
+        // Path only dependencies are left alone, and nothing changes when already up to date.
+        assert!(!set_dependency_version_req(&mut doc, "package_c", &version));
+        assert!(!set_dependency_version_req(&mut doc, "package_b", &version));
+    }
+
+    #[test]
+    fn test_registries_allowed_by_publish() {
+        let doc = r#"
+all = true
+none = false
+listed = ["crates-io", "internal"]
+"#
+        .parse::<Document>()
+        .unwrap();
+
+        let path = Path::new("Cargo.toml");
+        assert_eq!(registries_allowed_by(&doc["all"], path), vec!["crates-io"]);
+        assert!(registries_allowed_by(&doc["none"], path).is_empty());
+        assert_eq!(
+            registries_allowed_by(&doc["listed"], path),
+            vec!["crates-io", "internal"]
+        );
+    }
 }
@@ -0,0 +1,538 @@
+use super::bump_tree::node::BumpNode;
+use super::bump_tree::tree::{BumpTree, ReleaseChannel};
+use super::changelog::{self, CHANGELOG_FILE_NAME};
+use super::changeset::Changeset;
+use super::version_extension::BumpType;
+use super::workspace::Workspace;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// The bumps computed for a set of bump instructions, which can be written to a file, reviewed, and
+/// applied later by `apply`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BumpPlan {
+    pub release_channel: ReleaseChannel,
+    /// Bump instructions the plan was computed from, as passed on the command line
+    pub instructions: Vec<String>,
+    pub stable_branch: String,
+    pub prerelease_branch: String,
+    /// Sorted by release channel, then package name
+    pub bumps: Vec<PlannedBump>,
+    /// Changesets the instructions were read from, deleted from the branch of the release channel
+    /// in the same commit as the bumps
+    #[serde(default)]
+    pub changesets: Vec<PathBuf>,
+    /// Descriptions of the changes to each package, from changesets, for changelogs
+    #[serde(default)]
+    pub notes: BTreeMap<String, Vec<String>>,
+}
+
+/// A single package version bump on one release channel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedBump {
+    pub package: String,
+    pub branch: String,
+    pub channel: ReleaseChannel,
+    pub from: Version,
+    pub to: Version,
+    pub bump_type: BumpType,
+    /// Packages whose bumps caused this one, from the requested bump down to the direct
+    /// dependency. Empty when the bump was requested directly.
+    pub caused_by: Vec<String>,
+}
+
+/// An annotated tag to create for a stable bump.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedTag {
+    pub name: String,
+    pub message: String,
+}
+
+impl BumpPlan {
+    /// The bumps to apply on each release channel. Only prerelease bumps are applied when bumping
+    /// the prerelease channel.
+    pub fn from_tree(
+        tree: &BumpTree,
+        stable_workspace: &Workspace,
+        prerelease_workspace: &Workspace,
+        instructions: &[&str],
+        release_channel: ReleaseChannel,
+    ) -> Self {
+        let mut bumps = vec![];
+        if release_channel == ReleaseChannel::Stable {
+            for node in tree.highest_stable.values() {
+                let i = node.stable.as_ref().expect("must exist here");
+                bumps.push(PlannedBump {
+                    package: node.package_name(),
+                    branch: stable_workspace.branch_name.clone(),
+                    channel: ReleaseChannel::Stable,
+                    from: i.package.borrow().version(),
+                    to: i.next_version.clone(),
+                    bump_type: i.bump_type(),
+                    caused_by: cause_chain(&tree.highest_stable, node, |n| &n.stable_cause),
+                });
+            }
+        }
+        for node in tree.highest_prerelease.values() {
+            let i = node.prerelease.as_ref().expect("must exist here");
+            bumps.push(PlannedBump {
+                package: node.package_name(),
+                branch: prerelease_workspace.branch_name.clone(),
+                channel: ReleaseChannel::Prerelease,
+                from: i.package.borrow().version(),
+                to: i.next_version.clone(),
+                bump_type: i.bump_type(),
+                caused_by: cause_chain(&tree.highest_prerelease, node, |n| &n.prerelease_cause),
+            });
+        }
+        bumps.sort_by(|a, b| {
+            (a.channel == ReleaseChannel::Prerelease, &a.package)
+                .cmp(&(b.channel == ReleaseChannel::Prerelease, &b.package))
+        });
+
+        Self {
+            release_channel,
+            instructions: instructions.iter().map(|s| s.to_string()).collect(),
+            stable_branch: stable_workspace.branch_name.clone(),
+            prerelease_branch: prerelease_workspace.branch_name.clone(),
+            bumps,
+            changesets: vec![],
+            notes: BTreeMap::new(),
+        }
+    }
+
+    /// Records the changesets the instructions were read from, so they are consumed when the plan
+    /// is applied and their descriptions kept. Changesets none of whose bumps were applied, e.g.
+    /// because every package is already at the version, are left for a later bump.
+    pub fn record_changesets(&mut self, changesets: &[Changeset], applied: &[&str]) {
+        for changeset in changesets {
+            if !changeset
+                .bumps
+                .keys()
+                .any(|name| applied.contains(&name.as_str()))
+            {
+                log::info!(
+                    "None of the bumps in changeset {:?} were applied, keeping it",
+                    changeset.path
+                );
+                continue;
+            }
+            self.changesets.push(changeset.path.clone());
+            for name in changeset.bumps.keys() {
+                self.notes
+                    .entry(name.clone())
+                    .or_default()
+                    .push(changeset.description.clone());
+            }
+        }
+    }
+
+    /// Records notes passed on the command line, each a package name followed by a description of
+    /// its change, for changelogs.
+    pub fn record_notes(&mut self, raw_notes: &[&str]) -> Result<(), String> {
+        for raw_note in raw_notes {
+            let (name, description) =
+                raw_note
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| {
+                        format!(
+                            "Invalid note {:?}, expected a package name followed by a description",
+                            raw_note
+                        )
+                    })?;
+            if !self.bumps.iter().any(|b| b.package == name) {
+                return Err(format!("Note on {}, which isn't bumped", name));
+            }
+            self.notes
+                .entry(name.to_owned())
+                .or_default()
+                .push(description.trim().to_owned());
+        }
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read plan {:?}: {}", path, e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Invalid plan {:?}: {}", path, e))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let content = serde_json::to_string_pretty(self).expect("plans are always serializable");
+        fs::write(path, content + "\n")
+            .map_err(|e| format!("Failed to write plan {:?}: {}", path, e))?;
+        log::info!("📝 Wrote bump plan to {:?}", path);
+        Ok(())
+    }
+
+    /// Stages the planned versions on each workspace, after checking every package is still at
+    /// the version the plan bumps it from, along with a new section in the changelog of each
+    /// bumped package and the removal of the consumed changesets.
+    pub fn stage(
+        &self,
+        stable_workspace: &mut Workspace,
+        prerelease_workspace: &mut Workspace,
+    ) -> Result<(), String> {
+        let date = chrono::offset::Utc::now().format("%Y-%m-%d").to_string();
+        for (workspace, channel) in [
+            (stable_workspace, ReleaseChannel::Stable),
+            (prerelease_workspace, ReleaseChannel::Prerelease),
+        ] {
+            let mut versions = vec![];
+            for bump in self.bumps.iter().filter(|b| b.channel == channel) {
+                let package = workspace.packages.get(&bump.package).ok_or_else(|| {
+                    format!(
+                        "Package {} in the plan not found on branch {}",
+                        bump.package, workspace.branch_name
+                    )
+                })?;
+                let cur_version = package.borrow().version();
+                if cur_version != bump.from {
+                    return Err(format!(
+                        "Package {} on branch {} is v{}, but the plan bumps it from v{}. Recompute the plan.",
+                        bump.package, workspace.branch_name, cur_version, bump.from
+                    ));
+                }
+                versions.push((package, &bump.to));
+            }
+            workspace.set_package_versions(&versions);
+
+            for bump in self.bumps.iter().filter(|b| b.channel == channel) {
+                let entries = self.changelog_entries(bump);
+                if entries.is_empty() {
+                    log::info!(
+                        "No notes on the {} bump of {}, not adding a changelog section",
+                        bump.bump_type,
+                        bump.package
+                    );
+                    continue;
+                }
+                let path = workspace.packages[&bump.package]
+                    .borrow()
+                    .directory()
+                    .strip_prefix(&workspace.path)
+                    .expect("packages are in the workspace")
+                    .join(CHANGELOG_FILE_NAME);
+                let section = changelog::section(&bump.to, &date, &entries);
+                let content =
+                    changelog::insert_section(workspace.read_file(&path)?.as_deref(), &section);
+                workspace.stage_file(&path, content)?;
+            }
+            if channel == self.release_channel {
+                workspace.remove_files(&self.changesets)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tags for the stable bumps, named by a template where `{name}` and `{version}` are replaced
+    /// by the package name and new version, with messages giving the reason for each bump.
+    pub fn tags(&self, template: &str) -> Vec<PlannedTag> {
+        self.bumps
+            .iter()
+            .filter(|b| b.channel == ReleaseChannel::Stable)
+            .map(|bump| {
+                let reason = match bump.caused_by.is_empty() {
+                    true => match self
+                        .instructions
+                        .iter()
+                        .find(|i| i.split_whitespace().next() == Some(bump.package.as_str()))
+                    {
+                        Some(instruction) => format!("requested by `{}`", instruction),
+                        None => "requested".to_owned(),
+                    },
+                    false => format!("caused by the bump of {}", bump.caused_by.join(" → ")),
+                };
+                let mut message = format!(
+                    "{} v{}\n\n{} bump from v{}, {}\n",
+                    bump.package, bump.to, bump.bump_type, bump.from, reason
+                );
+                for note in self.notes.get(&bump.package).into_iter().flatten() {
+                    message.push_str(&format!("\n{}\n", note));
+                }
+                PlannedTag {
+                    name: template
+                        .replace("{name}", &bump.package)
+                        .replace("{version}", &bump.to.to_string()),
+                    message,
+                }
+            })
+            .collect()
+    }
+
+    /// Changelog entries of a bump: the notes on the package from changesets or `--note`, and the
+    /// dependency update which caused the bump, if it wasn't requested.
+    fn changelog_entries(&self, bump: &PlannedBump) -> Vec<String> {
+        let mut entries = self.notes.get(&bump.package).cloned().unwrap_or_default();
+        if let Some(dependency) = bump.caused_by.last() {
+            // Prefer the dependency's version on the same release channel
+            let version = self
+                .bumps
+                .iter()
+                .filter(|b| &b.package == dependency)
+                .min_by_key(|b| b.channel != bump.channel)
+                .map(|b| &b.to)
+                .expect("causes of bumps are bumped too");
+            entries.push(format!(
+                "Updated dependency `{}` to v{}",
+                dependency, version
+            ));
+        }
+        entries
+    }
+}
+
+/// Packages whose bumps caused the bump of `node` on one release channel, from the requested bump
+/// down to the direct dependency, following the cause of each bump on that channel.
+fn cause_chain(
+    nodes: &HashMap<String, Rc<BumpNode>>,
+    node: &BumpNode,
+    cause: fn(&BumpNode) -> &Option<String>,
+) -> Vec<String> {
+    let mut chain = vec![];
+    let mut next = cause(node);
+    while let Some(name) = next {
+        if chain.contains(name) {
+            break;
+        }
+        chain.push(name.clone());
+        next = match nodes.get(name) {
+            Some(n) => cause(n),
+            None => &None,
+        };
+    }
+    chain.reverse();
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::bump_tree::instruction::BumpInstruction;
+    use crate::common::mocks::get_mock_workspaces;
+    use tempdir::TempDir;
+
+    fn plan(
+        stable_workspace: &Workspace,
+        prerelease_workspace: &Workspace,
+        raw_bump_instruction: &str,
+    ) -> BumpPlan {
+        let instruction = BumpInstruction::from_str(
+            stable_workspace,
+            prerelease_workspace,
+            raw_bump_instruction,
+            ReleaseChannel::Stable,
+        )
+        .unwrap()
+        .unwrap();
+        let tree = BumpTree::new(
+            stable_workspace,
+            prerelease_workspace,
+            vec![instruction],
+            ReleaseChannel::Stable,
+        );
+        BumpPlan::from_tree(
+            &tree,
+            stable_workspace,
+            prerelease_workspace,
+            &[raw_bump_instruction],
+            ReleaseChannel::Stable,
+        )
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+
+        let stable_bump = |name: &str| {
+            plan.bumps
+                .iter()
+                .find(|b| b.package == name && b.channel == ReleaseChannel::Stable)
+                .unwrap()
+        };
+        assert_eq!(stable_bump("a").bump_type, BumpType::Major);
+        assert!(stable_bump("a").caused_by.is_empty());
+        assert_eq!(stable_bump("c").caused_by, vec!["a", "b"]);
+
+        let dir = TempDir::new("workspace-version-tools-plan").unwrap();
+        let path = dir.path().join("plan.json");
+        plan.write(&path).unwrap();
+        assert_eq!(BumpPlan::read(&path).unwrap(), plan);
+    }
+
+    #[test]
+    fn refuses_to_stage_when_versions_drifted() {
+        let (mut stable_workspace, mut prerelease_workspace) = get_mock_workspaces();
+        let plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+
+        stable_workspace.packages["c"]
+            .borrow_mut()
+            .set_version(&Version::new(9, 0, 0));
+        let err = plan
+            .stage(&mut stable_workspace, &mut prerelease_workspace)
+            .unwrap_err();
+        assert!(err.contains("Package c on branch"), "{}", err);
+    }
+
+    #[test]
+    fn names_tags_by_the_template() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+
+        let tags = plan.tags("{name}@{version}");
+        assert!(tags
+            .iter()
+            .all(|t| !t.name.contains("alpha") && t.name.contains('@')));
+        let tag = |name: &str| tags.iter().find(|t| t.name.starts_with(name)).unwrap();
+        assert_eq!(
+            tag("a@").message,
+            "a v1.0.0\n\nmajor bump from v0.1.0, requested by `a major`\n"
+        );
+        assert!(tag("c@")
+            .message
+            .contains("bump from v0.1.0, caused by the bump of a → b\n"));
+    }
+
+    #[test]
+    fn stages_changelog_sections() {
+        let (mut stable_workspace, mut prerelease_workspace) = get_mock_workspaces();
+        let mut plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+        plan.record_changesets(
+            &[Changeset {
+                path: PathBuf::from(".changes/a.md"),
+                bumps: BTreeMap::from([("a".to_owned(), "major".to_owned())]),
+                description: "Removed a call.".to_owned(),
+            }],
+            &["a"],
+        );
+        plan.changesets.clear();
+        plan.stage(&mut stable_workspace, &mut prerelease_workspace)
+            .unwrap();
+
+        let changelog = |package: &str| {
+            stable_workspace
+                .read_file(&Path::new(package).join(CHANGELOG_FILE_NAME))
+                .unwrap()
+                .unwrap()
+        };
+        assert!(
+            changelog("a").contains("## [1.0.0] - "),
+            "{}",
+            changelog("a")
+        );
+        assert!(changelog("a").contains("\n\n- Removed a call.\n"));
+        assert!(changelog("c").contains("\n\n- Updated dependency `b` to v"));
+    }
+
+    #[test]
+    fn consumes_only_changesets_with_applied_bumps() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let mut plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+        let changeset = |name: &str, description: &str| Changeset {
+            path: PathBuf::from(format!(".changes/{}.md", name)),
+            bumps: BTreeMap::from([(name.to_owned(), "major".to_owned())]),
+            description: description.to_owned(),
+        };
+        plan.record_changesets(
+            &[
+                changeset("a", "Removed a call."),
+                changeset("stable-only-2-0-0", "Already released."),
+            ],
+            &["a"],
+        );
+
+        assert_eq!(plan.changesets, vec![PathBuf::from(".changes/a.md")]);
+        assert_eq!(plan.notes.keys().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    fn skips_changelog_sections_without_notes() {
+        let (mut stable_workspace, mut prerelease_workspace) = get_mock_workspaces();
+        let mut plan = plan(&stable_workspace, &prerelease_workspace, "a major");
+        plan.record_notes(&["b Documented the b call."]).unwrap();
+        assert!(plan.record_notes(&["d"]).is_err());
+        assert!(plan
+            .record_notes(&["stable-only-1-0-0 Not bumped."])
+            .is_err());
+        plan.stage(&mut stable_workspace, &mut prerelease_workspace)
+            .unwrap();
+
+        let changelog = |package: &str| {
+            stable_workspace
+                .read_file(&Path::new(package).join(CHANGELOG_FILE_NAME))
+                .unwrap()
+        };
+        assert_eq!(changelog("a"), None);
+        let b = changelog("b").unwrap();
+        assert!(b.contains("\n\n- Documented the b call.\n"), "{}", b);
+        assert!(b.contains("- Updated dependency `a` to v1.0.0\n"), "{}", b);
+    }
+
+    #[test]
+    fn follows_the_cause_of_each_release_channel() {
+        let (stable_workspace, prerelease_workspace) = get_mock_workspaces();
+        let node = |name: &str, stable_cause: Option<&str>, prerelease_cause: Option<&str>| {
+            let instruction = |workspace: &Workspace, version: &str| BumpInstruction {
+                package: workspace.packages[name].clone(),
+                next_version: Version::parse(version).unwrap(),
+            };
+            Rc::new(BumpNode {
+                stable: Some(instruction(&stable_workspace, "0.2.0")),
+                prerelease: Some(instruction(&prerelease_workspace, "0.2.0-alpha.1")),
+                children: vec![],
+                stable_cause: stable_cause.map(|c| c.to_owned()),
+                prerelease_cause: prerelease_cause.map(|c| c.to_owned()),
+            })
+        };
+        // d's stable bump is decided by c, but its prerelease bump by b
+        let nodes = HashMap::from([
+            ("a".to_owned(), node("a", None, None)),
+            ("b".to_owned(), node("b", Some("a"), Some("a"))),
+            ("c".to_owned(), node("c", Some("b"), Some("b"))),
+            ("d".to_owned(), node("d", Some("c"), Some("b"))),
+        ]);
+        let mut tree = BumpTree::new(
+            &stable_workspace,
+            &prerelease_workspace,
+            vec![],
+            ReleaseChannel::Stable,
+        );
+        tree.highest_stable = nodes.clone();
+        tree.highest_prerelease = nodes;
+
+        let plan = BumpPlan::from_tree(
+            &tree,
+            &stable_workspace,
+            &prerelease_workspace,
+            &["a minor"],
+            ReleaseChannel::Stable,
+        );
+        let caused_by = |channel: ReleaseChannel| {
+            &plan
+                .bumps
+                .iter()
+                .find(|b| b.package == "d" && b.channel == channel)
+                .unwrap()
+                .caused_by
+        };
+        assert_eq!(caused_by(ReleaseChannel::Stable), &vec!["a", "b", "c"]);
+        assert_eq!(caused_by(ReleaseChannel::Prerelease), &vec!["a", "b"]);
+        let d_prerelease = plan
+            .bumps
+            .iter()
+            .find(|b| b.package == "d" && b.channel == ReleaseChannel::Prerelease)
+            .unwrap();
+        assert_eq!(
+            plan.changelog_entries(d_prerelease),
+            vec!["Updated dependency `b` to v0.2.0-alpha.1"]
+        );
+    }
+}
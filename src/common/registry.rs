@@ -0,0 +1,332 @@
+use semver::Version;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use toml_edit::Document;
+
+/// Name of the crates.io registry in `publish` fields and cargo's `--registry`
+pub const CRATES_IO: &str = "crates-io";
+
+/// Index of the crates.io registry
+pub const CRATES_IO_INDEX: &str = "sparse+https://index.crates.io/";
+
+/// Default user agent of requests to registries
+pub const DEFAULT_USER_AGENT: &str = concat!("workspace-version-tools/", env!("CARGO_PKG_VERSION"));
+
+/// The index of a registry, listing the published versions of each crate, e.g. crates.io's sparse
+/// index. See https://doc.rust-lang.org/cargo/reference/registry-index.html.
+pub struct RegistryIndex {
+    location: IndexLocation,
+}
+
+enum IndexLocation {
+    /// A sparse index served over HTTP, by its URL ending in `/`
+    Sparse {
+        url: String,
+        client: reqwest::Client,
+    },
+    /// A directory laid out like an index, e.g. a local stand-in for a registry
+    Local(PathBuf),
+}
+
+/// A line of an index file, describing a published version.
+#[derive(Deserialize)]
+struct IndexEntry {
+    vers: Version,
+    #[serde(default)]
+    yanked: bool,
+}
+
+impl RegistryIndex {
+    /// Opens the index at `url`, either `sparse+http(s)://...` or a `file://` URL or path to a
+    /// local directory. Requests are sent with `user_agent`.
+    pub fn new(url: &str, user_agent: &str) -> Result<Self, String> {
+        let sparse_url = url
+            .strip_prefix("sparse+")
+            .filter(|u| u.starts_with("http://") || u.starts_with("https://"));
+        let location = match (sparse_url, url.strip_prefix("file://")) {
+            (Some(url), _) => {
+                let client = reqwest::Client::builder()
+                    .user_agent(user_agent)
+                    .build()
+                    .map_err(|e| format!("Failed to create a client for index {}: {}", url, e))?;
+                IndexLocation::Sparse {
+                    url: format!("{}/", url.trim_end_matches('/')),
+                    client,
+                }
+            }
+            (None, Some(path)) => IndexLocation::Local(PathBuf::from(path)),
+            // Any other scheme, e.g. a git index over http(s):// or ssh://
+            (None, None) if url.contains("://") => {
+                return Err(format!(
+                    "Registry index {} isn't supported, only sparse+http(s):// indexes and local directories are",
+                    url
+                ))
+            }
+            (None, None) => IndexLocation::Local(PathBuf::from(url)),
+        };
+        Ok(Self { location })
+    }
+
+    /// Every published version of a crate, including yanked ones, which can't be published again.
+    pub async fn versions(&self, name: &str) -> Result<Vec<Version>, String> {
+        Ok(self
+            .entries(name)
+            .await?
+            .into_iter()
+            .map(|e| e.vers)
+            .collect())
+    }
+
+    /// The highest version of a crate which isn't yanked, or None if there isn't one.
+    pub async fn latest_version(&self, name: &str) -> Result<Option<Version>, String> {
+        Ok(self
+            .entries(name)
+            .await?
+            .into_iter()
+            .filter(|e| !e.yanked)
+            .map(|e| e.vers)
+            .max())
+    }
+
+    async fn entries(&self, name: &str) -> Result<Vec<IndexEntry>, String> {
+        let path = index_path(name);
+        let content = match &self.location {
+            IndexLocation::Sparse { url, client } => {
+                let url = format!("{}{}", url, path);
+                let response = client
+                    .get(&url)
+                    .send()
+                    .await
+                    .map_err(|e| format!("Failed to fetch {}: {}", url, e))?;
+                // Unpublished crates may be reported as missing or forbidden
+                if [
+                    reqwest::StatusCode::NOT_FOUND,
+                    reqwest::StatusCode::FORBIDDEN,
+                ]
+                .contains(&response.status())
+                {
+                    return Ok(vec![]);
+                }
+                response
+                    .error_for_status()
+                    .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+                    .text()
+                    .await
+                    .map_err(|e| format!("Failed to fetch {}: {}", url, e))?
+            }
+            IndexLocation::Local(dir) => {
+                let file = dir.join(&path);
+                if !file.is_file() {
+                    return Ok(vec![]);
+                }
+                std::fs::read_to_string(&file)
+                    .map_err(|e| format!("Failed to read {:?}: {}", file, e))?
+            }
+        };
+
+        content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                serde_json::from_str::<IndexEntry>(l)
+                    .map_err(|e| format!("Invalid index entry for {}: {}", name, e))
+            })
+            .collect()
+    }
+
+    /// Whether `version` of a crate is published.
+    pub async fn is_published(&self, name: &str, version: &Version) -> Result<bool, String> {
+        Ok(self.versions(name).await?.contains(version))
+    }
+}
+
+/// The indexes of the registries packages are published to, opened as they're first needed.
+pub struct Registries {
+    /// Directory the cargo config files are looked up from, i.e. the workspace's
+    path: PathBuf,
+    user_agent: String,
+    /// Index to use for every registry instead of its own, e.g. a local stand-in
+    index_override: Option<String>,
+    indexes: HashMap<String, RegistryIndex>,
+}
+
+impl Registries {
+    pub fn new(path: &Path, user_agent: &str, index_override: Option<&str>) -> Self {
+        Self {
+            path: path.to_owned(),
+            user_agent: user_agent.to_owned(),
+            index_override: index_override.map(|i| i.to_owned()),
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// The index of a registry, by its name in `publish` fields.
+    pub fn index(&mut self, registry: &str) -> Result<&RegistryIndex, String> {
+        if !self.indexes.contains_key(registry) {
+            let url = match &self.index_override {
+                Some(url) => url.clone(),
+                None => registry_index_url(&self.path, registry)?,
+            };
+            let index = RegistryIndex::new(&url, &self.user_agent)?;
+            self.indexes.insert(registry.to_owned(), index);
+        }
+        Ok(&self.indexes[registry])
+    }
+}
+
+/// The index URL of a registry, resolved like cargo does: crates.io's is known, and others are
+/// set by `CARGO_REGISTRIES_<NAME>_INDEX` or by `[registries.<name>] index` in the nearest cargo
+/// config file, looking from `path` up to the root and then in `$CARGO_HOME`.
+pub fn registry_index_url(path: &Path, registry: &str) -> Result<String, String> {
+    if registry == CRATES_IO {
+        return Ok(CRATES_IO_INDEX.to_owned());
+    }
+    let variable = format!(
+        "CARGO_REGISTRIES_{}_INDEX",
+        registry.to_uppercase().replace('-', "_")
+    );
+    if let Ok(url) = std::env::var(&variable) {
+        return Ok(url);
+    }
+    for file in cargo_config_files(path) {
+        let Ok(content) = std::fs::read_to_string(&file) else {
+            continue;
+        };
+        let config = content
+            .parse::<Document>()
+            .map_err(|e| format!("Failed to parse {:?}: {}", file, e))?;
+        if let Some(url) = config
+            .get("registries")
+            .and_then(|r| r.get(registry))
+            .and_then(|r| r.get("index"))
+            .and_then(|i| i.as_str())
+        {
+            return Ok(url.to_owned());
+        }
+    }
+    Err(format!(
+        "Registry {} isn't configured, set its index in [registries.{}] of .cargo/config.toml or with {}",
+        registry, registry, variable
+    ))
+}
+
+/// Cargo config files which apply in `path`, nearest first.
+fn cargo_config_files(path: &Path) -> Vec<PathBuf> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| Path::new(&h).join(".cargo")));
+    path.ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home)
+        .flat_map(|dir| [dir.join("config.toml"), dir.join("config")])
+        .collect()
+}
+
+/// Path of a crate's file in an index, e.g. `se/rd/serde`.
+fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+    let path = match name.len() {
+        1 => Path::new("1").join(&name),
+        2 => Path::new("2").join(&name),
+        3 => Path::new("3").join(&name[..1]).join(&name),
+        _ => Path::new(&name[..2]).join(&name[2..4]).join(&name),
+    };
+    path.to_string_lossy().replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn lays_out_index_paths() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("abc"), "3/a/abc");
+        assert_eq!(index_path("Serde"), "se/rd/serde");
+    }
+
+    #[tokio::test]
+    async fn reads_versions_from_a_local_index() {
+        let dir = TempDir::new("workspace-version-tools-index").unwrap();
+        fs::create_dir_all(dir.path().join("se/rd")).unwrap();
+        fs::write(
+            dir.path().join("se/rd/serde"),
+            "{\"name\":\"serde\",\"vers\":\"1.0.0\",\"yanked\":false}\n{\"name\":\"serde\",\"vers\":\"1.0.1\",\"yanked\":true}\n",
+        )
+        .unwrap();
+
+        let index = RegistryIndex::new(
+            &format!("file://{}", dir.path().display()),
+            DEFAULT_USER_AGENT,
+        )
+        .unwrap();
+        assert_eq!(
+            index.versions("serde").await.unwrap(),
+            vec![Version::new(1, 0, 0), Version::new(1, 0, 1)]
+        );
+        assert_eq!(
+            index.latest_version("serde").await.unwrap(),
+            Some(Version::new(1, 0, 0))
+        );
+        assert!(index
+            .is_published("serde", &Version::new(1, 0, 1))
+            .await
+            .unwrap());
+        assert!(index.versions("serde_json").await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_unsupported_index_schemes() {
+        for url in [
+            "https://github.com/rust-lang/crates.io-index",
+            "ssh://git@example.com/index.git",
+            "git://example.com/index.git",
+            "sparse+ssh://example.com/index/",
+        ] {
+            let error = RegistryIndex::new(url, DEFAULT_USER_AGENT)
+                .err()
+                .unwrap_or_else(|| panic!("{} was accepted", url));
+            assert!(error.contains("isn't supported"), "{}", error);
+        }
+        assert!(RegistryIndex::new("sparse+https://index.crates.io/", DEFAULT_USER_AGENT).is_ok());
+        assert!(RegistryIndex::new("file:///srv/index", DEFAULT_USER_AGENT).is_ok());
+        assert!(RegistryIndex::new("/srv/index", DEFAULT_USER_AGENT).is_ok());
+    }
+
+    #[test]
+    fn resolves_registry_indexes_from_cargo_config() {
+        let dir = TempDir::new("workspace-version-tools-config").unwrap();
+        let workspace = dir.path().join("workspace");
+        fs::create_dir_all(workspace.join(".cargo")).unwrap();
+        fs::create_dir_all(dir.path().join(".cargo")).unwrap();
+        fs::write(
+            workspace.join(".cargo/config.toml"),
+            "[registries.wvt-test-near]\nindex = \"sparse+https://near.example/\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join(".cargo/config"),
+            "[registries.wvt-test-near]\nindex = \"sparse+https://shadowed.example/\"\n\n[registries.wvt-test-far]\nindex = \"file:///srv/index\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            registry_index_url(&workspace, CRATES_IO).unwrap(),
+            CRATES_IO_INDEX
+        );
+        assert_eq!(
+            registry_index_url(&workspace, "wvt-test-near").unwrap(),
+            "sparse+https://near.example/"
+        );
+        assert_eq!(
+            registry_index_url(&workspace, "wvt-test-far").unwrap(),
+            "file:///srv/index"
+        );
+        assert!(registry_index_url(&workspace, "wvt-test-missing").is_err());
+    }
+}
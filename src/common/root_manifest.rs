@@ -0,0 +1,241 @@
+use super::diff::unified_diff;
+use super::package::{dependency_package_name, set_table_dependency_version_req};
+use semver::{Prerelease, Version};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Document, Item, Table, TableLike};
+
+/// Key of the `[workspace.metadata]` and `[package.metadata]` tables configuring this tool
+pub const METADATA_KEY: &str = "workspace-version-tools";
+
+/// A wrapper around the workspace root Cargo.toml toml_edit Document, holding the values members
+/// may inherit.
+#[derive(Debug)]
+pub struct RootManifest {
+    /// The doc, including any edits not yet written to disk
+    doc: Document,
+    /// Contents of the manifest on disk
+    original: String,
+    /// Path
+    path: PathBuf,
+}
+
+impl RootManifest {
+    fn workspace_package(&self) -> Option<&Table> {
+        self.doc
+            .get("workspace")
+            .and_then(|w| w.get("package"))
+            .and_then(|p| p.as_table())
+    }
+
+    fn workspace_package_mut(&mut self) -> Option<&mut Table> {
+        self.doc
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("package"))
+            .and_then(|p| p.as_table_mut())
+    }
+
+    /// The `[workspace]` `members` or `exclude` patterns.
+    fn workspace_paths(&self, key: &str) -> Vec<String> {
+        self.doc
+            .get("workspace")
+            .and_then(|w| w.get(key))
+            .and_then(|m| m.as_array())
+            .map(|m| {
+                m.iter()
+                    .filter_map(|m| m.as_str())
+                    .map(|m| m.to_owned())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Patterns matching the directories of workspace members, including the root package if
+    /// there is one.
+    pub fn members(&self) -> Vec<String> {
+        let mut members = self.workspace_paths("members");
+        if self.doc.contains_key("package") {
+            members.insert(0, "".to_owned());
+        }
+        members
+    }
+
+    /// Directories excluded from the workspace.
+    pub fn exclude(&self) -> Vec<String> {
+        self.workspace_paths("exclude")
+    }
+
+    /// The `[workspace.package]` publish setting, if one is set.
+    pub fn package_publish(&self) -> Option<&Item> {
+        self.workspace_package().and_then(|p| p.get("publish"))
+    }
+
+    /// The `[workspace.package]` version, if one is set.
+    pub fn package_version(&self) -> Option<Version> {
+        self.workspace_package()
+            .and_then(|p| p.get("version"))
+            .and_then(|v| v.as_str())
+            .map(|v| {
+                Version::parse(v).unwrap_or_else(|_| {
+                    panic!(
+                        "Failed to create Version from {:?} [workspace.package] version",
+                        self.path
+                    )
+                })
+            })
+    }
+
+    pub fn set_package_version(&mut self, version: &Version) {
+        log::debug!("Bumping [workspace.package] version to {}", version);
+
+        let path = self.path.clone();
+        let package = self
+            .workspace_package_mut()
+            .unwrap_or_else(|| panic!("Workspace {:?} is missing [workspace.package] table", path));
+        package["version"] = toml_edit::value(version.to_string());
+    }
+
+    /// This tool's `[workspace.metadata.workspace-version-tools]` table.
+    fn tool_metadata(&self) -> Option<&dyn TableLike> {
+        self.doc
+            .get("workspace")
+            .and_then(|w| w.get("metadata"))
+            .and_then(|m| m.get(METADATA_KEY))
+            .and_then(|t| t.as_table_like())
+    }
+
+    /// The configured identifier for new prerelease versions, e.g. `beta`.
+    pub fn prerelease_identifier(&self) -> Result<Option<Prerelease>, String> {
+        self.tool_metadata()
+            .and_then(|t| t.get("prerelease-identifier"))
+            .map(|i| {
+                i.as_str()
+                    .and_then(|i| Prerelease::new(i).ok())
+                    .ok_or_else(|| {
+                        format!(
+                            "Workspace {:?} has invalid prerelease-identifier {}",
+                            self.path, i
+                        )
+                    })
+            })
+            .transpose()
+    }
+
+    /// The configured template of the tags created for stable bumps, e.g. `{name}-v{version}`.
+    pub fn tag_template(&self) -> Result<Option<String>, String> {
+        self.tool_metadata()
+            .and_then(|t| t.get("tag-template"))
+            .map(|t| {
+                t.as_str().map(|t| t.to_owned()).ok_or_else(|| {
+                    format!("Workspace {:?} has invalid tag-template {}", self.path, t)
+                })
+            })
+            .transpose()
+    }
+
+    /// Version groups declared in `[workspace.metadata.workspace-version-tools.version-groups]`,
+    /// mapping group names to the names of their member packages.
+    pub fn version_groups(&self) -> Result<Vec<(String, Vec<String>)>, String> {
+        let Some(groups) = self.tool_metadata().and_then(|t| t.get("version-groups")) else {
+            return Ok(vec![]);
+        };
+        let invalid = || format!("Workspace {:?} has invalid version-groups", self.path);
+
+        groups
+            .as_table_like()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|(group, members)| {
+                let members = members
+                    .as_array()
+                    .ok_or_else(invalid)?
+                    .iter()
+                    .map(|m| m.as_str().map(|m| m.to_owned()).ok_or_else(invalid))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((group.to_owned(), members))
+            })
+            .collect()
+    }
+
+    /// The name of the package referred to by the `[workspace.dependencies]` entry `key`.
+    pub fn workspace_dependency_package_name(&self, key: &str) -> String {
+        self.doc
+            .get("workspace")
+            .and_then(|w| w.get("dependencies"))
+            .and_then(|d| d.get(key))
+            .map(|dep| dependency_package_name(key, dep))
+            .unwrap_or(key)
+            .to_owned()
+    }
+
+    /// Updates the `[workspace.dependencies]` entries on the package `dependency` which declare a
+    /// version requirement to require `version`.
+    pub fn set_dependency_version(&mut self, dependency: &str, version: &Version) {
+        let Some(dependencies) = self
+            .doc
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("dependencies"))
+            .and_then(|d| d.as_table_like_mut())
+        else {
+            return;
+        };
+
+        if set_table_dependency_version_req(dependencies, dependency, version) {
+            log::debug!(
+                "Updated [workspace.dependencies] requirement on {} to {}",
+                dependency,
+                version
+            );
+        }
+    }
+
+    /// Writes any edits made to the manifest to disk.
+    pub fn write(&mut self) {
+        let content = self.doc.to_string();
+        if content != self.original {
+            fs::write(self.path.clone(), &content)
+                .unwrap_or_else(|_| panic!("Failed to write to {:?}", self.path));
+            self.original = content;
+        }
+    }
+
+    /// Moves the manifest from the checkout of the repository at `from` to the one at `to`.
+    pub fn relocate(&mut self, from: &Path, to: &Path) {
+        self.path = to.join(
+            self.path
+                .strip_prefix(from)
+                .unwrap_or_else(|_| panic!("Manifest {:?} is outside of {:?}", self.path, from)),
+        );
+    }
+
+    /// A unified diff of the edits not yet written to disk, with paths relative to `base`.
+    pub fn diff(&self, base: &Path) -> Option<String> {
+        unified_diff(
+            self.path.strip_prefix(base).unwrap_or(&self.path),
+            &self.original,
+            &self.doc.to_string(),
+        )
+    }
+
+    /// Loads the root manifest from its contents, read from `path`.
+    pub fn new(path: PathBuf, content: String) -> Result<Self, String> {
+        let doc = content
+            .parse::<Document>()
+            .map_err(|e| format!("Workspace Cargo.toml at path {:?} is invalid: {}", path, e))?;
+
+        Ok(Self {
+            doc,
+            original: content,
+            path,
+        })
+    }
+}
+
+/// Whether a manifest value is inherited from the workspace, i.e. `key.workspace = true`.
+pub fn is_inherited(item: &Item) -> bool {
+    item.get("workspace")
+        .and_then(|w| w.as_bool())
+        .unwrap_or(false)
+}
@@ -0,0 +1,192 @@
+use crate::common::git::move_branch;
+use git2::{build::CheckoutBuilder, BranchType, Oid, Repository, ResetType};
+use std::path::{Path, PathBuf};
+
+/// Records the git state of a workspace before bumps are applied, so it can be restored if
+/// applying them fails partway through.
+pub struct Transaction {
+    /// Repository path
+    path: PathBuf,
+    /// Branch checked out before the transaction began, which is returned to on rollback. None
+    /// when the transaction doesn't touch the checkout, e.g. when working in worktrees.
+    original_branch: Option<String>,
+    /// Heads of the branches modified by the transaction before it touched them, or None for
+    /// branches created by it
+    branch_heads: Vec<(String, Option<Oid>)>,
+    /// Tags created by the transaction
+    tags: Vec<String>,
+}
+
+impl Transaction {
+    pub fn begin(path: &Path, original_branch: Option<&str>) -> Result<Self, String> {
+        let mut transaction = Self {
+            path: path.to_owned(),
+            original_branch: original_branch.map(|b| b.to_owned()),
+            branch_heads: vec![],
+            tags: vec![],
+        };
+        if let Some(original_branch) = original_branch {
+            transaction.track_branch(original_branch)?;
+        }
+        Ok(transaction)
+    }
+
+    fn open_repository(&self) -> Result<Repository, String> {
+        Repository::open(&self.path)
+            .map_err(|e| format!("Failed to open repository at {:?}: {}", self.path, e))
+    }
+
+    /// Records the head of a branch before it is committed to or created.
+    pub fn track_branch(&mut self, branch_name: &str) -> Result<(), String> {
+        if self
+            .branch_heads
+            .iter()
+            .any(|(name, _)| name == branch_name)
+        {
+            return Ok(());
+        }
+
+        let repo = self.open_repository()?;
+        let head = repo
+            .find_branch(branch_name, BranchType::Local)
+            .ok()
+            .and_then(|b| b.get().target());
+        self.branch_heads.push((branch_name.to_owned(), head));
+        Ok(())
+    }
+
+    /// Records a tag before it is created, so it's deleted on rollback.
+    pub fn track_tag(&mut self, name: &str) {
+        self.tags.push(name.to_owned());
+    }
+
+    /// Deletes the tags created, restores the heads of tracked branches, deletes the branches
+    /// created, and checks the original branch, if any, back out with a clean working tree.
+    /// Returns a description of each step.
+    ///
+    /// Branches deleted from the remote can't be restored.
+    pub fn rollback(&self) -> Result<Vec<String>, String> {
+        let repo = self.open_repository()?;
+        let mut rolled_back = vec![];
+
+        for name in self.tags.iter() {
+            if repo.find_reference(&format!("refs/tags/{}", name)).is_ok() {
+                repo.tag_delete(name)
+                    .map_err(|e| format!("Failed to delete tag {}: {}", name, e))?;
+                rolled_back.push(format!("Deleted tag {}", name));
+            }
+        }
+
+        for (branch_name, head) in self.branch_heads.iter() {
+            let Some(head) = head else {
+                continue;
+            };
+            let refname = format!("refs/heads/{}", branch_name);
+            let cur_head = repo.refname_to_id(&refname).ok();
+            if cur_head != Some(*head) {
+                let reset = match (&self.original_branch, cur_head) {
+                    // Without an original branch to check back out, the checkout is moved back
+                    // along with the branch in case it has the branch checked out
+                    (None, Some(cur_head)) => move_branch(&repo, branch_name, cur_head, *head),
+                    _ => repo
+                        .reference(&refname, *head, true, "workspace-version-tools rollback")
+                        .map(|_| ()),
+                };
+                reset.map_err(|e| format!("Failed to reset branch {}: {}", branch_name, e))?;
+                rolled_back.push(format!("Reset branch {} to {}", branch_name, head));
+            }
+        }
+
+        if let Some(original_branch) = &self.original_branch {
+            let original_head = repo
+                .revparse_single(original_branch)
+                .map_err(|e| format!("Failed to find branch {}: {}", original_branch, e))?;
+            repo.set_head(&format!("refs/heads/{}", original_branch))
+                .and_then(|_| {
+                    repo.reset(
+                        &original_head,
+                        ResetType::Hard,
+                        Some(CheckoutBuilder::default().force().remove_untracked(true)),
+                    )
+                })
+                .map_err(|e| format!("Failed to check out branch {}: {}", original_branch, e))?;
+            rolled_back.push(format!(
+                "Checked out branch {} with a clean working tree",
+                original_branch
+            ));
+        }
+
+        for (branch_name, head) in self.branch_heads.iter() {
+            if head.is_some() {
+                continue;
+            }
+            if let Ok(mut branch) = repo.find_branch(branch_name, BranchType::Local) {
+                branch
+                    .delete()
+                    .map_err(|e| format!("Failed to delete branch {}: {}", branch_name, e))?;
+                rolled_back.push(format!("Deleted branch {}", branch_name));
+            }
+        }
+
+        Ok(rolled_back)
+    }
+
+    /// Rolls back after `error`, logging what was rolled back, and returns the error to report.
+    pub fn abort(&self, error: String) -> String {
+        log::error!("❌ {}", error);
+        log::info!("⏪ Rolling back changes...");
+        match self.rollback() {
+            Ok(rolled_back) => {
+                for step in rolled_back.iter() {
+                    log::info!("⏪ {}", step);
+                }
+                format!("{} (rolled back: {})", error, rolled_back.join(", "))
+            }
+            Err(e) => format!("{} (failed to roll back: {})", error, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::git::stage_and_commit_all_changes;
+    use crate::common::mocks::init_mock_repository;
+    use std::fs;
+
+    fn commit_file(repo: &Repository, branch: &str, content: &str) {
+        let path = repo.workdir().unwrap().join("Cargo.toml");
+        fs::write(path, content).unwrap();
+        stage_and_commit_all_changes(repo, branch, content).unwrap();
+    }
+
+    #[test]
+    fn rolls_back_commits_and_created_branches() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let original_head = repo.refname_to_id("refs/heads/main").unwrap();
+        let original_manifest = fs::read_to_string(path.join("Cargo.toml")).unwrap();
+
+        let mut transaction = Transaction::begin(&path, Some("main")).unwrap();
+        commit_file(&repo, "main", "bumped");
+        transaction.track_branch("propagate").unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("propagate", &head, false).unwrap();
+        repo.set_head("refs/heads/propagate").unwrap();
+        fs::write(path.join("Cargo.toml"), "partial edit").unwrap();
+
+        let rolled_back = transaction.rollback().unwrap();
+
+        assert_eq!(rolled_back.len(), 3);
+        assert_eq!(
+            repo.refname_to_id("refs/heads/main").unwrap(),
+            original_head
+        );
+        assert!(repo.find_branch("propagate", BranchType::Local).is_err());
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(
+            fs::read_to_string(path.join("Cargo.toml")).unwrap(),
+            original_manifest
+        );
+    }
+}
@@ -1,11 +1,16 @@
 use semver::{Prerelease, Version};
-use std::{cmp::Ordering, str::FromStr};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum BumpType {
     Major,
     Minor,
     Patch,
+    /// A new iteration of the same prerelease, e.g. `1.3.0-alpha.4` -> `1.3.0-alpha.5`
+    Prerelease,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -20,40 +25,58 @@ impl BumpType {
             "major" => Ok(BumpType::Major),
             "minor" => Ok(BumpType::Minor),
             "patch" => Ok(BumpType::Patch),
+            "prerelease" => Ok(BumpType::Prerelease),
             _ => Err(format!("Invalid bump type: {}", s)),
         }
     }
 }
 
+impl Display for BumpType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            BumpType::Major => write!(f, "major"),
+            BumpType::Minor => write!(f, "minor"),
+            BumpType::Patch => write!(f, "patch"),
+            BumpType::Prerelease => write!(f, "prerelease"),
+        }
+    }
+}
+
 impl PartialOrd for BumpType {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        match (self, other) {
-            (BumpType::Major, BumpType::Major) => Some(Ordering::Equal),
-            (BumpType::Major, _) => Some(Ordering::Greater),
-
-            (BumpType::Minor, BumpType::Major) => Some(Ordering::Less),
-            (BumpType::Minor, BumpType::Minor) => Some(Ordering::Equal),
-            (BumpType::Minor, BumpType::Patch) => Some(Ordering::Greater),
-
-            (BumpType::Patch, BumpType::Patch) => Some(Ordering::Equal),
-            (BumpType::Patch, _) => Some(Ordering::Less),
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for BumpType {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        match (self, other) {
+            (BumpType::Major, BumpType::Major) => Ordering::Equal,
+            (BumpType::Major, _) => Ordering::Greater,
+
+            (BumpType::Minor, BumpType::Major) => Ordering::Less,
+            (BumpType::Minor, BumpType::Minor) => Ordering::Equal,
+            (BumpType::Minor, BumpType::Patch | BumpType::Prerelease) => Ordering::Greater,
+
+            (BumpType::Patch, BumpType::Major | BumpType::Minor) => Ordering::Less,
+            (BumpType::Patch, BumpType::Patch) => Ordering::Equal,
+            (BumpType::Patch, BumpType::Prerelease) => Ordering::Greater,
+
+            (BumpType::Prerelease, BumpType::Prerelease) => Ordering::Equal,
+            (BumpType::Prerelease, _) => Ordering::Less,
+        }
     }
 }
 
 pub trait VersionExtension {
-    fn bump(self: &Self, bump_type: BumpType, end_user_initiated: EndUserInitiated) -> Version;
-    fn with_prerelease(self: &Self) -> Version;
+    fn bump(&self, bump_type: BumpType, end_user_initiated: EndUserInitiated) -> Version;
+    fn bump_prerelease_iteration(&self) -> Option<Version>;
+    fn with_prerelease(&self, identifier: &Prerelease) -> Version;
+    fn prerelease_identifier(&self) -> Option<Prerelease>;
 }
 
 impl VersionExtension for Version {
-    fn bump(self: &Self, bump_type: BumpType, end_user_initiated: EndUserInitiated) -> Version {
+    fn bump(&self, bump_type: BumpType, end_user_initiated: EndUserInitiated) -> Version {
         let mut next_version = self.clone();
         match bump_type {
             BumpType::Major => match end_user_initiated {
@@ -85,15 +108,51 @@ impl VersionExtension for Version {
             BumpType::Patch => {
                 next_version.patch += 1;
             }
+            // A released version can't be iterated on, so needs at least a patch bump
+            BumpType::Prerelease => match self.bump_prerelease_iteration() {
+                Some(iteration) => next_version = iteration,
+                None => next_version.patch += 1,
+            },
         };
         next_version
     }
 
-    fn with_prerelease(self: &Self) -> Version {
+    /// Increments the trailing number of the prerelease, starting one if there isn't any, e.g.
+    /// `1.3.0-alpha.4` -> `1.3.0-alpha.5`. None if the version isn't a prerelease.
+    fn bump_prerelease_iteration(&self) -> Option<Version> {
+        if self.pre.is_empty() {
+            return None;
+        }
+        let mut identifiers = self.pre.split('.').collect::<Vec<_>>();
+        let iteration = match identifiers.last().and_then(|i| i.parse::<u64>().ok()) {
+            Some(n) => {
+                identifiers.pop();
+                n + 1
+            }
+            None => 1,
+        };
+        let iteration = iteration.to_string();
+        identifiers.push(&iteration);
+
         let mut next_version = self.clone();
-        next_version.pre = Prerelease::from_str("alpha").expect("valid");
+        next_version.pre = Prerelease::new(&identifiers.join(".")).expect("valid");
+        Some(next_version)
+    }
+
+    fn with_prerelease(&self, identifier: &Prerelease) -> Version {
+        let mut next_version = self.clone();
+        next_version.pre = identifier.clone();
         next_version
     }
+
+    /// The label of a prerelease version, e.g. `rc` for `1.0.0-rc.1`.
+    fn prerelease_identifier(&self) -> Option<Prerelease> {
+        self.pre
+            .split('.')
+            .next()
+            .filter(|identifier| !identifier.is_empty())
+            .map(|identifier| Prerelease::new(identifier).expect("part of a valid prerelease"))
+    }
 }
 
 #[test]
@@ -108,4 +167,41 @@ fn bump_type_ordering() {
     assert!(BumpType::Minor == BumpType::Minor);
     assert!(BumpType::Patch == BumpType::Patch);
     assert!(std::cmp::max(BumpType::Major, BumpType::Minor) == BumpType::Major);
+    assert!(BumpType::Patch > BumpType::Prerelease);
+    assert!(BumpType::Prerelease < BumpType::Minor);
+    assert!(BumpType::Prerelease == BumpType::Prerelease);
+}
+
+#[test]
+fn prerelease_bump() {
+    let bump = |v: &str| {
+        Version::parse(v)
+            .unwrap()
+            .bump(BumpType::Prerelease, EndUserInitiated::Yes)
+            .to_string()
+    };
+    assert_eq!(bump("1.3.0-alpha"), "1.3.0-alpha.1");
+    assert_eq!(bump("1.3.0-alpha.4"), "1.3.0-alpha.5");
+    assert_eq!(bump("1.3.0-rc.9"), "1.3.0-rc.10");
+    assert_eq!(bump("1.3.0-beta.2.x"), "1.3.0-beta.2.x.1");
+    assert_eq!(bump("1.3.0"), "1.3.1");
+    assert_eq!(Version::new(1, 3, 0).bump_prerelease_iteration(), None);
+}
+
+#[test]
+fn prerelease_identifier() {
+    let identifier = |v: &str| Version::parse(v).unwrap().prerelease_identifier();
+    assert_eq!(identifier("1.0.0"), None);
+    assert_eq!(
+        identifier("1.0.0-alpha"),
+        Some(Prerelease::new("alpha").unwrap())
+    );
+    assert_eq!(
+        identifier("1.0.0-rc.1"),
+        Some(Prerelease::new("rc").unwrap())
+    );
+    assert_eq!(
+        identifier("1.0.0-beta.2.3"),
+        Some(Prerelease::new("beta").unwrap())
+    );
 }
@@ -1,33 +1,82 @@
 use super::{
-    git::{checkout_local_branch, create_and_checkout_branch, stage_and_commit_all_changes},
+    diff::unified_diff,
+    git::{
+        checkout_local_branch, create_and_checkout_branch, create_branch,
+        stage_and_commit_all_changes,
+    },
+    manifest_source::{expand_members, GitTree, ManifestSource, WorkingTree},
     package::Package,
+    root_manifest::RootManifest,
+    worktree::Worktree,
 };
 use crate::common::{
-    git::{get_current_branch_name, is_working_tree_clean},
-    package::find_direct_dependents,
+    git::{changed_files, get_current_branch_name, is_working_tree_clean},
+    package::{find_direct_dependents, set_str_keeping_decor},
 };
-use cargo_metadata::MetadataCommand;
 use git2::Repository;
+use semver::{Prerelease, Version};
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
-    path::PathBuf,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
     process::Command,
     rc::Rc,
 };
+use toml_edit::Document;
+
+/// Prerelease identifier used when the workspace doesn't configure one
+const DEFAULT_PRERELEASE_IDENTIFIER: &str = "alpha";
+/// Tag template used when the workspace doesn't configure one
+const DEFAULT_TAG_TEMPLATE: &str = "{name}-v{version}";
+
+/// A file, relative to the workspace root, to write or delete along with the manifests.
+struct StagedFile {
+    path: PathBuf,
+    /// Contents before staging, empty if there was no such file
+    original: String,
+    /// None to delete the file
+    content: Option<String>,
+}
 
 /// An in-memory representation of the workspace members
 pub struct Workspace {
     /// Members of the workspace
     pub packages: HashMap<String, Rc<RefCell<Package>>>,
+    /// Root Cargo.toml
+    pub root: Rc<RefCell<RootManifest>>,
+    /// Identifier for packages being given a new prerelease version, e.g. `alpha`
+    pub prerelease_identifier: Prerelease,
+    /// Template of the tags created for stable bumps, where `{name}` and `{version}` are replaced
+    /// by the package name and new version
+    pub tag_template: String,
+    /// Named groups of packages which always share the same version
+    pub version_groups: BTreeMap<String, BTreeSet<String>>,
     /// Workspace path
     pub path: PathBuf,
+    /// Contents of the Cargo.lock on disk, if there is one
+    lockfile: Option<String>,
+    /// Whether the workspace was read from the working tree, rather than from the branch in git
+    from_working_tree: bool,
+    /// Files other than manifests to write along with them, e.g. changelogs
+    staged_files: Vec<StagedFile>,
     /// Git branch
     pub branch_name: String,
     /// Git remote
     pub remote_name: String,
 }
 
+/// Where a workspace is loaded from.
+#[derive(PartialEq)]
+enum LoadFrom {
+    /// The working tree, which must have no uncommitted changes as it's about to be written to
+    CleanWorkingTree,
+    /// The working tree, including any uncommitted changes
+    WorkingTree,
+    /// The branch as committed to git
+    Git,
+}
+
 impl Workspace {
     #[cfg(test)]
     pub fn new_test_workspace(workspace_path: PathBuf) -> Result<Self, String> {
@@ -35,7 +84,10 @@ impl Workspace {
 
         log::info!("⏳Building workspace for path {:?}...", &cargo_toml_path,);
 
-        let w = Self::create_packages_and_workspace(workspace_path, "no-git", "no-git")?;
+        let source = WorkingTree {
+            path: workspace_path.clone(),
+        };
+        let w = Self::create_packages_and_workspace(&source, workspace_path, "no-git", "no-git")?;
 
         log::info!("Workspace built ✅");
 
@@ -46,16 +98,55 @@ impl Workspace {
         workspace_path: PathBuf,
         branch_name: Option<&str>,
         remote_name: &str,
+    ) -> Result<Self, String> {
+        Self::load(
+            workspace_path,
+            branch_name,
+            remote_name,
+            LoadFrom::CleanWorkingTree,
+        )
+    }
+
+    /// Loads the workspace as committed to the branch, leaving any uncommitted changes in the
+    /// working tree alone.
+    pub fn new_from_git(
+        workspace_path: PathBuf,
+        branch_name: Option<&str>,
+        remote_name: &str,
+    ) -> Result<Self, String> {
+        Self::load(workspace_path, branch_name, remote_name, LoadFrom::Git)
+    }
+
+    /// Loads the workspace including any uncommitted changes, for commands which only read it.
+    pub fn new_read_only(
+        workspace_path: PathBuf,
+        branch_name: Option<&str>,
+        remote_name: &str,
+    ) -> Result<Self, String> {
+        Self::load(
+            workspace_path,
+            branch_name,
+            remote_name,
+            LoadFrom::WorkingTree,
+        )
+    }
+
+    fn load(
+        workspace_path: PathBuf,
+        branch_name: Option<&str>,
+        remote_name: &str,
+        from: LoadFrom,
     ) -> Result<Self, String> {
         let repo = Repository::open(&workspace_path)
             .map_err(|e| format!("Failed to open repository at {:?}: {}", &workspace_path, e))?;
 
         let cargo_toml_path = workspace_path.join("Cargo.toml");
+        let current_branch_name = get_current_branch_name(&repo).ok();
         let branch_name = match branch_name {
             Some(branch_name) => branch_name.to_owned(),
-            None => get_current_branch_name(&repo)
-                .expect("Failed to get current branch name")
-                .to_owned(),
+            None => current_branch_name
+                .clone()
+                .expect("Failed to get current branch name"),
         };
 
         log::info!(
@@ -64,12 +155,10 @@ impl Workspace {
             &branch_name
         );
 
-        if !is_working_tree_clean(&repo) {
+        if from == LoadFrom::CleanWorkingTree && !is_working_tree_clean(&repo) {
             return Err("Workspace is not clean. Please commit or stash your changes.".to_owned());
         }
 
-        checkout_local_branch(&repo, &branch_name).map_err(|e| e.to_string())?;
-
         // Disable pulling for now...
         // log::info!(
         //     "Pulling latest changes from remote '{} {}'",
@@ -83,8 +172,24 @@ impl Workspace {
         //     do_fetch(&repo, &[&branch_name], &mut remote).map_err(|e| format!("{}", e))?;
         // do_fast_forward(&repo, &branch_name, fetch_commit).map_err(|e| format!("{}", e))?;
 
-        let w =
-            Self::create_packages_and_workspace(workspace_path, branch_name.as_str(), remote_name)?;
+        // Other branches, or any other ref, are read straight from git so the working tree is
+        // left alone
+        let w = if from != LoadFrom::Git && current_branch_name.as_ref() == Some(&branch_name) {
+            let source = WorkingTree {
+                path: workspace_path.clone(),
+            };
+            Self::create_packages_and_workspace(&source, workspace_path, &branch_name, remote_name)?
+        } else {
+            let source = GitTree::new(&repo, &branch_name)?;
+            let mut w = Self::create_packages_and_workspace(
+                &source,
+                workspace_path,
+                &branch_name,
+                remote_name,
+            )?;
+            w.from_working_tree = false;
+            w
+        };
 
         log::info!("Workspace built ✅");
 
@@ -92,39 +197,47 @@ impl Workspace {
     }
 
     fn create_packages_and_workspace(
+        source: &dyn ManifestSource,
         workspace_path: PathBuf,
         branch_name: &str,
         remote_name: &str,
     ) -> Result<Self, String> {
         let cargo_toml_path = workspace_path.join("Cargo.toml");
+        let content = source
+            .read(Path::new("Cargo.toml"))?
+            .ok_or_else(|| format!("No workspace Cargo.toml at {:?}", &cargo_toml_path))?;
+        let root = Rc::new(RefCell::new(RootManifest::new(cargo_toml_path, content)?));
 
         // Create the Packages
-        let metadata = MetadataCommand::new()
-            .manifest_path(&cargo_toml_path)
-            .exec()
-            .map_err(|e| format!("Failed to load workspace at {:?}: {}", &cargo_toml_path, e))?;
-
-        let cargo_metadata_members = metadata.workspace_packages();
-        let workspace_member_names = cargo_metadata_members
-            .iter()
-            .map(|p| p.name.clone())
+        let (members, exclude) = {
+            let root = root.borrow();
+            (root.members(), root.exclude())
+        };
+        let mut workspace_package_map = HashMap::new();
+        for member in expand_members(source, &members, &exclude)? {
+            let manifest_path = member.join("Cargo.toml");
+            let content = source
+                .read(&manifest_path)?
+                .expect("members are only found by their Cargo.toml");
+            let package = Package::new(
+                workspace_path.join(&manifest_path),
+                content,
+                &root,
+                "no-git",
+            )
+            .map_err(|e| format!("Failed to load package at {:?}: {}", manifest_path, e))?;
+            log::debug!("Loaded package {}", package);
+            workspace_package_map.insert(package.name(), Rc::new(RefCell::new(package)));
+        }
+        let workspace_member_names = workspace_package_map
+            .keys()
+            .cloned()
             .collect::<HashSet<_>>();
-        let workspace_package_map = cargo_metadata_members
-            .iter()
-            .map(|p| {
-                Package::new(&p, &workspace_member_names, "no-git")
-                    .map_err(|e| format!("Failed to load package at {:?}: {}", p, e))
-            })
-            .fold(HashMap::new(), |mut acc, package_result| {
-                match package_result {
-                    Ok(package) => {
-                        log::debug!("Loaded package {}", package);
-                        acc.insert(package.name(), Rc::new(RefCell::new(package)));
-                    }
-                    Err(e) => log::error!("{}", e),
-                };
-                acc
-            });
+        for package in workspace_package_map.values() {
+            package
+                .borrow_mut()
+                .retain_workspace_dependencies(&workspace_member_names);
+        }
 
         // Compute and set the dependencies and dependents
         let workspace_deps_string_set = workspace_package_map
@@ -136,7 +249,7 @@ impl Workspace {
                         .borrow()
                         .direct_workspace_dependencies()
                         .iter()
-                        .map(|dep| dep.clone())
+                        .cloned()
                         .collect::<HashSet<_>>(),
                 )
             })
@@ -160,9 +273,30 @@ impl Workspace {
                 .set_direct_dependents(direct_dependents);
         }
 
+        let prerelease_identifier = root
+            .borrow()
+            .prerelease_identifier()?
+            .unwrap_or_else(|| Prerelease::new(DEFAULT_PRERELEASE_IDENTIFIER).expect("valid"));
+
+        let tag_template = root
+            .borrow()
+            .tag_template()?
+            .unwrap_or_else(|| DEFAULT_TAG_TEMPLATE.to_owned());
+
+        let version_groups = Self::version_groups(&root.borrow(), &workspace_package_map)?;
+
+        let lockfile = source.read(Path::new("Cargo.lock"))?;
+
         let w = Workspace {
             packages: workspace_package_map,
+            root,
+            prerelease_identifier,
+            tag_template,
+            version_groups,
             path: workspace_path,
+            lockfile,
+            from_working_tree: true,
+            staged_files: vec![],
             branch_name: branch_name.to_owned(),
             remote_name: remote_name.to_owned(),
         };
@@ -172,6 +306,281 @@ impl Workspace {
         Ok(w)
     }
 
+    /// Collects the version groups declared in the root manifest and in package manifests, making
+    /// sure every member exists and belongs to a single group.
+    fn version_groups(
+        root: &RootManifest,
+        packages: &HashMap<String, Rc<RefCell<Package>>>,
+    ) -> Result<BTreeMap<String, BTreeSet<String>>, String> {
+        let mut declared = root.version_groups()?;
+        for (name, package) in packages.iter() {
+            if let Some(group) = package.borrow().version_group()? {
+                declared.push((group, vec![name.clone()]));
+            }
+        }
+
+        let mut version_groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let mut package_groups: HashMap<String, String> = HashMap::new();
+        for (group, members) in declared {
+            for member in members {
+                if !packages.contains_key(&member) {
+                    return Err(format!(
+                        "Version group {} member {} is not a workspace member",
+                        group, member
+                    ));
+                }
+                match package_groups.insert(member.clone(), group.clone()) {
+                    Some(other) if other != group => {
+                        return Err(format!(
+                            "Package {} is in both version groups {} and {}",
+                            member, other, group
+                        ))
+                    }
+                    _ => {}
+                }
+                version_groups
+                    .entry(group.clone())
+                    .or_default()
+                    .insert(member);
+            }
+        }
+
+        Ok(version_groups)
+    }
+
+    /// The version group a package belongs to.
+    pub fn version_group(&self, package: &str) -> Option<&str> {
+        self.version_groups
+            .iter()
+            .find(|(_, members)| members.contains(package))
+            .map(|(group, _)| group.as_str())
+    }
+
+    /// Stages the versions of member packages, and updates the requirements every member and
+    /// `[workspace.dependencies]` declares on them to the new versions.
+    ///
+    /// Packages inheriting `[workspace.package]` version keep doing so when every inheriting
+    /// package moves to the same version, in which case the shared version is bumped instead.
+    pub fn set_package_versions(&self, versions: &[(&Rc<RefCell<Package>>, &Version)]) {
+        let shared_version = self.shared_next_version(versions);
+        if let Some(version) = shared_version {
+            self.root.borrow_mut().set_package_version(version);
+        }
+
+        for (package, version) in versions {
+            if shared_version.is_none() || !package.borrow().inherits_version() {
+                package.borrow_mut().set_version(version);
+            }
+            let name = package.borrow().name();
+            self.root
+                .borrow_mut()
+                .set_dependency_version(&name, version);
+            for member in self.packages.values() {
+                member.borrow_mut().set_dependency_version(&name, version);
+            }
+        }
+    }
+
+    /// The version to move `[workspace.package]` to, if every member inheriting it is being set
+    /// to the same version.
+    fn shared_next_version<'v>(
+        &self,
+        versions: &[(&Rc<RefCell<Package>>, &'v Version)],
+    ) -> Option<&'v Version> {
+        let inheriting = self
+            .packages
+            .values()
+            .filter(|p| p.borrow().inherits_version())
+            .map(|p| p.borrow().name())
+            .collect::<HashSet<_>>();
+        let (bumped, next_versions): (HashSet<_>, HashSet<_>) = versions
+            .iter()
+            .filter(|(p, _)| p.borrow().inherits_version())
+            .map(|(p, v)| (p.borrow().name(), *v))
+            .unzip();
+
+        match (bumped == inheriting, next_versions.len()) {
+            (true, 1) => next_versions.into_iter().next(),
+            _ => None,
+        }
+    }
+
+    /// Moves the workspace to another checkout of the repository at `path`, e.g. a worktree, so
+    /// manifests are written and changes committed there.
+    pub fn relocate(&mut self, path: PathBuf) {
+        self.root.borrow_mut().relocate(&self.path, &path);
+        for package in self.packages.values() {
+            package.borrow_mut().relocate(&self.path, &path);
+        }
+        self.path = path;
+    }
+
+    /// Contents of a file, relative to the workspace root, as staged or as the workspace was read,
+    /// or None if there is no such file.
+    pub fn read_file(&self, path: &Path) -> Result<Option<String>, String> {
+        if let Some(staged) = self.staged_files.iter().find(|f| f.path == path) {
+            return Ok(staged.content.clone());
+        }
+        if self.from_working_tree {
+            let source = WorkingTree {
+                path: self.path.clone(),
+            };
+            return source.read(path);
+        }
+        let repo = self.open_repository();
+        let source = GitTree::new(&repo, &self.branch_name)?;
+        source.read(path)
+    }
+
+    /// Stages the contents of a file, relative to the workspace root, which is written along with
+    /// the manifests.
+    pub fn stage_file(&mut self, path: &Path, content: String) -> Result<(), String> {
+        self.stage(path, Some(content))
+    }
+
+    /// Stages the deletion of files, relative to the workspace root, e.g. consumed changesets,
+    /// which are deleted along with writing the manifests.
+    pub fn remove_files(&mut self, paths: &[PathBuf]) -> Result<(), String> {
+        for path in paths {
+            if self.read_file(path)?.is_none() {
+                return Err(format!(
+                    "{:?} isn't on branch {}, was it already removed?",
+                    path, self.branch_name
+                ));
+            }
+            self.stage(path, None)?;
+        }
+        Ok(())
+    }
+
+    fn stage(&mut self, path: &Path, content: Option<String>) -> Result<(), String> {
+        match self.staged_files.iter_mut().find(|f| f.path == path) {
+            Some(staged) => staged.content = content,
+            None => {
+                let original = self.read_file(path)?.unwrap_or_default();
+                self.staged_files.push(StagedFile {
+                    path: path.to_owned(),
+                    original,
+                    content,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the staged edits to every manifest to disk, along with the other staged files.
+    pub fn write_manifests(&self) {
+        self.root.borrow_mut().write();
+        for package in self.packages.values() {
+            package.borrow_mut().write();
+        }
+        for staged in self.staged_files.iter() {
+            let path = self.path.join(&staged.path);
+            match &staged.content {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)
+                            .unwrap_or_else(|_| panic!("Failed to create {:?}", parent));
+                    }
+                    fs::write(&path, content)
+                        .unwrap_or_else(|_| panic!("Failed to write to {:?}", path));
+                }
+                None if path.exists() => {
+                    fs::remove_file(&path)
+                        .unwrap_or_else(|_| panic!("Failed to remove {:?}", path));
+                }
+                None => {}
+            }
+        }
+    }
+
+    /// A unified diff of every manifest, staged file, and the Cargo.lock, that writing the staged
+    /// edits would change.
+    pub fn diff(&self) -> Result<String, String> {
+        let mut diffs = vec![];
+        diffs.extend(self.root.borrow().diff(&self.path));
+        for package in self.packages.values() {
+            diffs.extend(package.borrow().diff(&self.path));
+        }
+        for staged in self.staged_files.iter() {
+            diffs.extend(unified_diff(
+                &staged.path,
+                &staged.original,
+                staged.content.as_deref().unwrap_or_default(),
+            ));
+        }
+        // Keep output stable between runs
+        diffs.sort();
+
+        if let Some(lockfile) = &self.lockfile {
+            diffs.extend(unified_diff(
+                &PathBuf::from("Cargo.lock"),
+                lockfile,
+                &self.staged_lockfile(lockfile)?,
+            ));
+        }
+
+        Ok(diffs.concat())
+    }
+
+    /// The Cargo.lock with the entries of members set to their staged versions.
+    fn staged_lockfile(&self, lockfile: &str) -> Result<String, String> {
+        let mut doc = lockfile
+            .parse::<Document>()
+            .map_err(|e| format!("Cargo.lock in {:?} is invalid: {}", self.path, e))?;
+        let versions = self
+            .packages
+            .iter()
+            .map(|(name, p)| (name.clone(), p.borrow().version().to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let Some(entries) = doc
+            .get_mut("package")
+            .and_then(|p| p.as_array_of_tables_mut())
+        else {
+            return Ok(lockfile.to_owned());
+        };
+        // Members are the only entries without a source
+        let mut locked_versions = HashMap::new();
+        for entry in entries.iter_mut() {
+            let Some(name) = entry
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(|n| n.to_owned())
+            else {
+                continue;
+            };
+            let Some(version) = versions
+                .get(&name)
+                .filter(|_| !entry.contains_key("source"))
+            else {
+                continue;
+            };
+            if let Some(locked_version) = entry.get("version").and_then(|v| v.as_str()) {
+                locked_versions.insert(format!("{} {}", name, locked_version), name.clone());
+            }
+            set_str_keeping_decor(&mut entry["version"], version);
+        }
+
+        // Dependencies are listed as `name`, or `name version` when ambiguous
+        for entry in entries.iter_mut() {
+            let Some(dependencies) = entry.get_mut("dependencies").and_then(|d| d.as_array_mut())
+            else {
+                continue;
+            };
+            for dependency in dependencies.iter_mut() {
+                let Some(name) = dependency.as_str().and_then(|d| locked_versions.get(d)) else {
+                    continue;
+                };
+                let decor = dependency.decor().clone();
+                *dependency = format!("{} {}", name, versions[name]).into();
+                *dependency.decor_mut() = decor;
+            }
+        }
+
+        Ok(doc.to_string())
+    }
+
     pub fn stage_and_commit_all(&self, message: &str) -> Result<(), String> {
         let repo = self.open_repository();
         stage_and_commit_all_changes(&repo, &self.branch_name, message)
@@ -179,6 +588,138 @@ impl Workspace {
         Ok(())
     }
 
+    /// Writes the staged edits and commits them to `branch_name` from a temporary worktree, leaving
+    /// the checkout at the workspace path untouched, unless it has `branch_name` checked out, in
+    /// which case it's moved to the new commit along with the branch.
+    pub fn commit_in_worktree(&mut self, branch_name: &str, message: &str) -> Result<(), String> {
+        let worktree = Worktree::add(&self.path, branch_name)?;
+        let path = self.path.clone();
+        self.relocate(worktree.path.clone());
+
+        self.write_manifests();
+        let committed = self
+            .update_lockfile()
+            .and_then(|_| self.stage_and_commit_all(message));
+
+        self.relocate(path);
+        committed.and_then(|_| worktree.finish())
+    }
+
+    /// Creates a branch off the head of the workspace branch, without checking it out.
+    pub fn create_branch(&self, branch_name: &str) -> Result<(), String> {
+        let repo = self.open_repository();
+        let commit = repo
+            .revparse_single(&self.branch_name)
+            .and_then(|o| o.peel_to_commit())
+            .map_err(|e| e.to_string())?;
+        create_branch(&repo, &self.remote_name, branch_name, &commit).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Names of the packages, ordered so each comes after its workspace dependencies, e.g. to
+    /// publish them in. Packages in the same layer of the dependency graph are sorted by name.
+    pub fn topological_order(&self) -> Result<Vec<String>, String> {
+        let mut remaining = self
+            .packages
+            .iter()
+            .map(|(name, p)| {
+                let dependencies = p
+                    .borrow()
+                    .direct_workspace_dependencies()
+                    .iter()
+                    .cloned()
+                    .collect::<BTreeSet<_>>();
+                (name.clone(), dependencies)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut order = vec![];
+        while !remaining.is_empty() {
+            let ready = remaining
+                .iter()
+                .filter(|(_, dependencies)| dependencies.is_empty())
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            if ready.is_empty() {
+                return Err(format!(
+                    "Packages {} depend on each other in a cycle",
+                    remaining.keys().cloned().collect::<Vec<_>>().join(", ")
+                ));
+            }
+            for name in ready {
+                remaining.remove(&name);
+                for dependencies in remaining.values_mut() {
+                    dependencies.remove(&name);
+                }
+                order.push(name);
+            }
+        }
+        Ok(order)
+    }
+
+    /// Workspace packages with files which differ between `since` (e.g. a tag or branch) and the
+    /// working tree, by name, along with their changed files relative to the workspace root.
+    pub fn changed_packages(&self, since: &str) -> Result<BTreeMap<String, Vec<PathBuf>>, String> {
+        let repo = self.open_repository();
+        let files = changed_files(&repo, since)
+            .map_err(|e| format!("Failed to diff the working tree against {}: {}", since, e))?;
+        self.packages_owning(&repo, &files)
+    }
+
+    /// Groups files, given relative to the root of the repository, by the package they belong to,
+    /// with paths relative to the workspace root. Files outside of any package are left out.
+    ///
+    /// Files belong to the package in the deepest directory containing them, so files of packages
+    /// nested in another package's directory aren't counted as belonging to it.
+    pub fn packages_owning(
+        &self,
+        repo: &Repository,
+        files: &[PathBuf],
+    ) -> Result<BTreeMap<String, Vec<PathBuf>>, String> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| "Can't find changes in a bare repository".to_owned())?;
+        let canonicalize = |p: &Path| {
+            p.canonicalize()
+                .map_err(|e| format!("Failed to resolve {:?}: {}", p, e))
+        };
+        let workspace_dir = canonicalize(&self.path)?;
+        let workspace_in_repo = workspace_dir
+            .strip_prefix(canonicalize(workdir)?)
+            .map_err(|_| format!("Workspace {:?} is outside of its repository", self.path))?
+            .to_owned();
+
+        let package_dirs = self
+            .packages
+            .iter()
+            .map(|(name, p)| {
+                let dir = p
+                    .borrow()
+                    .directory()
+                    .strip_prefix(&self.path)
+                    .expect("packages are in the workspace")
+                    .to_owned();
+                (name.clone(), dir)
+            })
+            .collect::<Vec<_>>();
+
+        let mut owned: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for file in files {
+            let Ok(file) = file.strip_prefix(&workspace_in_repo) else {
+                continue;
+            };
+            let owner = package_dirs
+                .iter()
+                .filter(|(_, dir)| file.starts_with(dir))
+                .max_by_key(|(_, dir)| dir.components().count());
+            match owner {
+                Some((name, _)) => owned.entry(name.clone()).or_default().push(file.to_owned()),
+                None => log::debug!("{:?} isn't in a workspace package", file),
+            }
+        }
+        Ok(owned)
+    }
+
     pub fn open_repository(&self) -> Repository {
         Repository::open(&self.path).expect("Failed to open repository")
     }
@@ -189,7 +730,7 @@ impl Workspace {
         let output = Command::new("cargo")
             .arg("metadata")
             .arg("--manifest-path")
-            .arg(&self.path.join("Cargo.toml"))
+            .arg(self.path.join("Cargo.toml"))
             .current_dir(&self.path)
             .output()
             .map_err(|e| format!("Failed to execute command: {}", e))?;
@@ -215,3 +756,326 @@ impl Workspace {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::git::stage_and_commit_all_changes;
+    use crate::common::mocks::{copy_mock_workspace, init_mock_repository};
+    use std::fs;
+
+    fn read_manifest(workspace: &Workspace, package: &str) -> String {
+        fs::read_to_string(workspace.path.join(package).join("Cargo.toml")).unwrap()
+    }
+
+    #[test]
+    fn resolves_inherited_versions() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+
+        let x = workspace.packages.get("x").unwrap().borrow();
+        assert!(x.inherits_version());
+        assert_eq!(x.version(), Version::parse("1.0.0").unwrap());
+        let z = workspace.packages.get("z").unwrap().borrow();
+        assert!(!z.inherits_version());
+        assert_eq!(z.version(), Version::parse("0.1.0").unwrap());
+    }
+
+    #[test]
+    fn reads_version_groups() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        assert_eq!(
+            workspace.version_groups,
+            BTreeMap::from([(
+                "xy".to_owned(),
+                BTreeSet::from(["x".to_owned(), "y".to_owned()])
+            )])
+        );
+        assert_eq!(workspace.version_group("y"), Some("xy"));
+        assert_eq!(workspace.version_group("z"), None);
+    }
+
+    #[test]
+    fn reads_prerelease_identifier() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        assert_eq!(
+            workspace.prerelease_identifier,
+            Prerelease::new("beta").unwrap()
+        );
+
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("stable_workspace")).unwrap();
+        assert_eq!(
+            workspace.prerelease_identifier,
+            Prerelease::new("alpha").unwrap()
+        );
+    }
+
+    #[test]
+    fn workspace_dependencies_are_edges() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+
+        let y = workspace.packages.get("y").unwrap().borrow();
+        assert!(y.direct_workspace_dependencies().contains("x"));
+        let z = workspace.packages.get("z").unwrap().borrow();
+        assert!(z.direct_workspace_dependencies().contains("y"));
+        let x = workspace.packages.get("x").unwrap().borrow();
+        assert_eq!(
+            x.direct_workspace_dependents()
+                .map(|p| p.borrow().name())
+                .collect::<Vec<_>>(),
+            vec!["y".to_owned()]
+        );
+    }
+
+    #[test]
+    fn orders_packages_after_their_dependencies() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("stable_workspace")).unwrap();
+
+        let order = workspace.topological_order().unwrap();
+        assert_eq!(order.len(), workspace.packages.len());
+        let position = |name: &str| order.iter().position(|p| p == name).unwrap();
+        assert!(position("a") < position("b"));
+        assert!(position("b") < position("c"));
+        assert!(position("c") < position("d"));
+    }
+
+    #[test]
+    fn bumps_shared_version_when_all_inheriting_packages_move_together() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        let x = workspace.packages.get("x").unwrap();
+        let y = workspace.packages.get("y").unwrap();
+        let next_version = Version::parse("1.1.0").unwrap();
+
+        workspace.set_package_versions(&[(x, &next_version), (y, &next_version)]);
+        workspace.write_manifests();
+
+        assert_eq!(
+            workspace.root.borrow().package_version(),
+            Some(next_version.clone())
+        );
+        assert!(x.borrow().inherits_version());
+        assert!(y.borrow().inherits_version());
+        assert_eq!(x.borrow().version(), next_version);
+        assert!(read_manifest(&workspace, "x").contains("version.workspace = true"));
+        assert!(read_manifest(&workspace, "y").contains("x.workspace = true"));
+        let root_manifest = read_manifest(&workspace, "");
+        assert!(root_manifest.contains(r#"version = "1.1.0""#));
+        assert!(root_manifest.contains(r#"x = { path = "x", version = "1.1.0" }"#));
+        assert!(root_manifest
+            .contains(r#"renamed-y = { package = "y", path = "y", version = "1.1.0" }"#));
+    }
+
+    #[test]
+    fn uninherits_version_when_bumped_alone() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        let x = workspace.packages.get("x").unwrap();
+        let y = workspace.packages.get("y").unwrap();
+        let next_version = Version::parse("2.0.0").unwrap();
+
+        workspace.set_package_versions(&[(x, &next_version)]);
+        workspace.write_manifests();
+
+        assert_eq!(
+            workspace.root.borrow().package_version(),
+            Some(Version::parse("1.0.0").unwrap())
+        );
+        assert!(!x.borrow().inherits_version());
+        assert_eq!(x.borrow().version(), next_version);
+        assert!(y.borrow().inherits_version());
+        assert_eq!(y.borrow().version(), Version::parse("1.0.0").unwrap());
+        assert!(read_manifest(&workspace, "x").contains(r#"version = "2.0.0""#));
+        assert!(read_manifest(&workspace, "").contains(r#"x = { path = "x", version = "2.0.0" }"#));
+    }
+
+    #[test]
+    fn diffs_staged_edits_without_writing_them() {
+        let workspace =
+            Workspace::new_test_workspace(copy_mock_workspace("inherited_workspace")).unwrap();
+        let z = workspace.packages.get("z").unwrap();
+
+        workspace.set_package_versions(&[(z, &Version::parse("0.2.0").unwrap())]);
+
+        assert!(read_manifest(&workspace, "z").contains(r#"version = "0.1.0""#));
+        assert_eq!(
+            workspace.diff().unwrap(),
+            r#"--- a/z/Cargo.toml
++++ b/z/Cargo.toml
+@@ -1,6 +1,6 @@
+ [package]
+ name = "z"
+-version = "0.1.0"
++version = "0.2.0"
+ edition.workspace = true
+ 
+ # See more keys and their definitions at https://doc.rust-lang.org/cargo/reference/manifest.html
+--- a/Cargo.lock
++++ b/Cargo.lock
+@@ -15,7 +15,7 @@
+ 
+ [[package]]
+ name = "z"
+-version = "0.1.0"
++version = "0.2.0"
+ dependencies = [
+  "y",
+ ]
+"#
+        );
+
+        workspace.write_manifests();
+        assert!(read_manifest(&workspace, "z").contains(r#"version = "0.2.0""#));
+        assert!(!workspace.diff().unwrap().contains("z/Cargo.toml"));
+    }
+
+    #[test]
+    fn fails_to_load_with_an_invalid_member() {
+        let path = copy_mock_workspace("inherited_workspace");
+        fs::write(path.join("z").join("Cargo.toml"), "[package]\n").unwrap();
+
+        let err = Workspace::new_test_workspace(path).err().unwrap();
+        assert!(err.contains("Failed to load package at"), "{}", err);
+        assert!(err.contains("has no name"), "{}", err);
+    }
+
+    #[test]
+    fn reads_other_branches_from_git() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("stable", &head, false).unwrap();
+        let z_manifest = path.join("z").join("Cargo.toml");
+        let bumped = fs::read_to_string(&z_manifest)
+            .unwrap()
+            .replace(r#"version = "0.1.0""#, r#"version = "0.2.0""#);
+        fs::write(&z_manifest, &bumped).unwrap();
+        stage_and_commit_all_changes(&repo, "main", "Bump z").unwrap();
+
+        let workspace = Workspace::new(path.clone(), Some("stable"), "origin").unwrap();
+
+        let z = workspace.packages.get("z").unwrap().borrow();
+        assert_eq!(z.version(), Version::parse("0.1.0").unwrap());
+        assert!(z.direct_workspace_dependencies().contains("y"));
+        assert_eq!(workspace.packages.len(), 3);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(fs::read_to_string(&z_manifest).unwrap(), bumped);
+    }
+
+    #[test]
+    fn finds_packages_changed_since_a_ref() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        fs::write(path.join("z").join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        stage_and_commit_all_changes(&repo, "main", "Change z").unwrap();
+        fs::write(path.join("x").join("src").join("extra.rs"), "").unwrap();
+        fs::write(path.join("y").join("src").join("main.rs"), "").unwrap();
+        fs::write(path.join("README.md"), "").unwrap();
+
+        // Uncommitted changes are reported rather than refused
+        let workspace = Workspace::new_read_only(path, None, "origin").unwrap();
+
+        assert_eq!(
+            workspace.changed_packages("HEAD~1").unwrap(),
+            BTreeMap::from([
+                ("x".to_owned(), vec![PathBuf::from("x/src/extra.rs")]),
+                ("y".to_owned(), vec![PathBuf::from("y/src/main.rs")]),
+                ("z".to_owned(), vec![PathBuf::from("z/src/main.rs")]),
+            ])
+        );
+        assert!(workspace.changed_packages("not-a-ref").is_err());
+    }
+
+    #[test]
+    fn commits_in_worktree_without_touching_checkout() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("stable", &head, false).unwrap();
+        let uncommitted = path.join("z").join("Cargo.toml");
+        fs::write(&uncommitted, "uncommitted").unwrap();
+
+        let mut workspace =
+            Workspace::new_from_git(path.clone(), Some("stable"), "origin").unwrap();
+        let z = workspace.packages.get("z").unwrap().clone();
+        workspace.set_package_versions(&[(&z, &Version::parse("0.2.0").unwrap())]);
+        workspace.commit_in_worktree("stable", "Bump z").unwrap();
+
+        let committed = GitTree::new(&repo, "stable")
+            .unwrap()
+            .read(Path::new("z/Cargo.toml"))
+            .unwrap()
+            .unwrap();
+        assert!(committed.contains(r#"version = "0.2.0""#));
+        assert_eq!(workspace.path, path);
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(fs::read_to_string(&uncommitted).unwrap(), "uncommitted");
+        assert!(repo.worktrees().unwrap().is_empty());
+    }
+
+    #[test]
+    fn commits_in_worktree_to_the_checked_out_branch() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let uncommitted = path.join("x").join("src").join("main.rs");
+        fs::write(&uncommitted, "uncommitted").unwrap();
+
+        let mut workspace = Workspace::new_from_git(path.clone(), None, "origin").unwrap();
+        let z = workspace.packages.get("z").unwrap().clone();
+        workspace.set_package_versions(&[(&z, &Version::parse("0.2.0").unwrap())]);
+        workspace.commit_in_worktree("main", "Bump z").unwrap();
+
+        // The checkout moves along with the branch, keeping uncommitted changes to other files
+        let committed = GitTree::new(&repo, "main")
+            .unwrap()
+            .read(Path::new("z/Cargo.toml"))
+            .unwrap()
+            .unwrap();
+        assert!(committed.contains(r#"version = "0.2.0""#));
+        assert_eq!(repo.head().unwrap().shorthand(), Some("main"));
+        assert_eq!(
+            fs::read_to_string(path.join("z").join("Cargo.toml")).unwrap(),
+            committed
+        );
+        assert_eq!(fs::read_to_string(&uncommitted).unwrap(), "uncommitted");
+        let statuses = repo.statuses(None).unwrap();
+        let changed = statuses
+            .iter()
+            .map(|s| s.path().unwrap().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(changed, vec!["x/src/main.rs"]);
+        assert!(repo.worktrees().unwrap().is_empty());
+        assert_eq!(
+            repo.branches(None).unwrap().count(),
+            1,
+            "the worktree's branch is deleted"
+        );
+    }
+
+    #[test]
+    fn refuses_to_overwrite_an_existing_worktree_branch() {
+        let path = init_mock_repository("inherited_workspace");
+        let repo = Repository::open(&path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.branch("workspace-version-tools-main", &head, false)
+            .unwrap();
+
+        let mut workspace = Workspace::new_from_git(path.clone(), None, "origin").unwrap();
+        let z = workspace.packages.get("z").unwrap().clone();
+        workspace.set_package_versions(&[(&z, &Version::parse("0.2.0").unwrap())]);
+        assert!(workspace.commit_in_worktree("main", "Bump z").is_err());
+
+        // The user's branch is kept as it was
+        assert_eq!(
+            repo.refname_to_id("refs/heads/workspace-version-tools-main")
+                .unwrap(),
+            head.id()
+        );
+        assert_eq!(repo.refname_to_id("refs/heads/main").unwrap(), head.id());
+    }
+}
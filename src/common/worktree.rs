@@ -0,0 +1,116 @@
+use crate::common::git::move_branch;
+use git2::{BranchType, Oid, Repository, WorktreeAddOptions, WorktreePruneOptions};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+/// A branch checked out in a temporary git worktree, so it can be edited and committed to without
+/// touching the main checkout. The worktree has a temporary branch of its own, as the branch may
+/// already be checked out elsewhere, which the branch is moved to by `finish`. The worktree and
+/// its branch are removed when dropped.
+pub struct Worktree {
+    /// Path of the main repository
+    repo_path: PathBuf,
+    /// Name git knows the worktree and its temporary branch by
+    name: String,
+    /// Path of the worktree's checkout
+    pub path: PathBuf,
+    /// Branch being committed to
+    branch_name: String,
+    /// Head of the branch when the worktree was added
+    base: Oid,
+    /// Directory holding the worktree, deleted once it's dropped
+    _dir: TempDir,
+}
+
+impl Worktree {
+    /// Checks out the head of `branch_name` in a new worktree.
+    pub fn add(repo_path: &Path, branch_name: &str) -> Result<Self, String> {
+        let repo = Repository::open(repo_path)
+            .map_err(|e| format!("Failed to open repository at {:?}: {}", repo_path, e))?;
+        let base = repo
+            .find_branch(branch_name, BranchType::Local)
+            .map_err(|e| format!("Failed to find branch {}: {}", branch_name, e))?
+            .get()
+            .peel_to_commit()
+            .map_err(|e| format!("Failed to find the head of branch {}: {}", branch_name, e))?;
+
+        let name = format!(
+            "workspace-version-tools-{}",
+            branch_name.replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+        );
+        if repo.find_branch(&name, BranchType::Local).is_ok() {
+            return Err(format!(
+                "Branch {} already exists, delete or rename it to apply bumps in a worktree",
+                name
+            ));
+        }
+        let reference = repo
+            .branch(&name, &base, false)
+            .map_err(|e| format!("Failed to create branch {}: {}", name, e))?
+            .into_reference();
+        let dir = TempDir::new("workspace-version-tools-worktree")
+            .map_err(|e| format!("Failed to create a directory for a worktree: {}", e))?;
+        let path = dir.path().join(&name);
+
+        log::info!(
+            "⏳Checking out branch {} in a worktree at {:?}",
+            branch_name,
+            path
+        );
+        let worktree = Self {
+            repo_path: repo_path.to_owned(),
+            name,
+            path,
+            branch_name: branch_name.to_owned(),
+            base: base.id(),
+            _dir: dir,
+        };
+        let mut options = WorktreeAddOptions::new();
+        options.reference(Some(&reference));
+        repo.worktree(&worktree.name, &worktree.path, Some(&options))
+            .map_err(|e| {
+                format!(
+                    "Failed to check out branch {} in a worktree: {}",
+                    branch_name, e
+                )
+            })?;
+
+        Ok(worktree)
+    }
+
+    /// Moves the branch to the commits made in the worktree, along with the main checkout if the
+    /// branch is checked out there.
+    pub fn finish(&self) -> Result<(), String> {
+        let repo = Repository::open(&self.repo_path)
+            .map_err(|e| format!("Failed to open repository at {:?}: {}", self.repo_path, e))?;
+        let head = repo
+            .refname_to_id(&format!("refs/heads/{}", self.name))
+            .map_err(|e| format!("Failed to find branch {}: {}", self.name, e))?;
+        if head == self.base {
+            return Ok(());
+        }
+        move_branch(&repo, &self.branch_name, self.base, head)
+            .map_err(|e| format!("Failed to update branch {}: {}", self.branch_name, e))
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        // The temporary branch was created by `add`, so is deleted even if the worktree wasn't
+        let removed = Repository::open(&self.repo_path).and_then(|repo| {
+            let pruned = repo.find_worktree(&self.name).and_then(|worktree| {
+                worktree.prune(Some(
+                    WorktreePruneOptions::new().valid(true).working_tree(true),
+                ))
+            });
+            let deleted = repo
+                .find_branch(&self.name, BranchType::Local)
+                .and_then(|mut branch| branch.delete());
+            pruned.and(deleted)
+        });
+        match removed {
+            Ok(()) => log::info!("🧹 Removed worktree at {:?}", self.path),
+            Err(e) => log::warn!("Failed to remove worktree at {:?}: {}", self.path, e),
+        }
+    }
+}
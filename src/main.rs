@@ -7,9 +7,18 @@
 //! release channels where the prerelease channel is periodically merged into stable.
 
 use clap::{value_parser, ArgAction};
+use common::bump_tree::tree::ReleaseChannel;
+use common::changeset::{merge_instructions, read_changesets};
+use common::conventional_commits::infer_bump_instructions;
+use common::output::OutputFormat;
+use common::plan::BumpPlan;
+use common::registry::{Registries, DEFAULT_USER_AGENT};
+use common::version_extension::BumpType;
 use common::workspace::Workspace;
 use env_logger::Env;
-use std::{borrow::BorrowMut, path::PathBuf};
+use semver::Prerelease;
+use std::path::PathBuf;
+use std::time::Duration;
 
 mod commands;
 mod common;
@@ -34,10 +43,12 @@ async fn run() -> Result<(), String> {
         .args(&[
             clap::arg!(-w --workspace <PATH> "Workspace path").required(true).value_parser(value_parser!(String)),
             clap::arg!(-r --"git-remote" [REMOTE] "Git remote").value_parser(value_parser!(String)).default_value("origin"),
+            clap::arg!(--"user-agent" <USER_AGENT> "User agent of requests to registries").value_parser(value_parser!(String)).default_value(DEFAULT_USER_AGENT),
+            clap::arg!(--output [FORMAT] "Output format, text or json. JSON output is a single document on stdout, with logs left on stderr").value_parser(["text", "json"]).default_value("text"),
         ])
         .subcommand(
             clap::command!("sync")
-                .about("Sync local Cargo.toml files to match crates.io version")
+                .about("Sync local Cargo.toml files to match the latest version published to the registry each package's publish field allows, crates.io by default")
         )
         .subcommand(
             clap::command!("make-at-least-stable")
@@ -48,14 +59,42 @@ async fn run() -> Result<(), String> {
                 .subcommand_required(true)
                 .about("Bump a package in the workspace")
                 .args(&[
-                    clap::arg!(-b --"bump-instruction" <BUMP_INSTRUCTION> "Package and type of bump to make to it, e.g. \"pallet-balances minor\". Supports being passed multiple times to bump multiple packages at once.")
-                        .required(true)
+                    clap::arg!(-b --"bump-instruction" <BUMP_INSTRUCTION> "Package and type of bump to make to it, e.g. \"pallet-balances minor\". Bump types are major, minor, patch, or prerelease for a new iteration of an existing prerelease (-alpha.1 -> -alpha.2). An exact version can be given instead, e.g. \"pallet-balances =2.0.0\". Supports being passed multiple times to bump multiple packages at once.")
+                        .required_unless_present_any(["since", "from-changesets"])
+                        .action(ArgAction::Append)
+                        .value_parser(value_parser!(String)),
+                    clap::arg!(--since <REF> "Infer the bump instructions instead, from the Conventional Commits touching each package on the branch since a tag, branch or commit. feat: commits need a minor bump, fix: commits a patch bump, and breaking changes (feat!: or a BREAKING CHANGE: footer) a major bump.")
+                        .conflicts_with("bump-instruction")
+                        .value_parser(value_parser!(String)),
+                    clap::arg!(-c --"from-changesets" [BOOL] "Read the bump instructions instead from the changesets committed to the branch under .changes/, keeping the highest bump of each package. Changesets with a bump which is applied are deleted in the same commit as the bumps, and those whose bumps are all unnecessary are kept.")
+                        .conflicts_with_all(["bump-instruction", "since"])
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(-n --note <NOTE> "Package and description of its change for its changelog, e.g. \"pallet-balances Removed the deprecated transfer call\". Supports being passed multiple times. Packages bumped without notes, and not because of a dependency, get no changelog section.")
                         .action(ArgAction::Append)
                         .value_parser(value_parser!(String)),
-                    clap::arg!(-d --"dry-run" [BOOL] "Whether to dry-run the change")
+                    clap::arg!(-d --"dry-run" [BOOL] "Whether to dry-run the change, printing a diff of every file which would change instead of applying it")
                         .default_value("false")
                         .default_missing_value("true")
-                        .value_parser(value_parser!(bool))
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(-t --worktree [BOOL] "Whether to apply the bumps in temporary git worktrees, leaving any uncommitted changes in the current checkout untouched. The checkout only moves if its branch is bumped")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(-g --tag [BOOL] "Whether to create an annotated tag for each package bumped on the stable branch, refusing to apply the bumps if any of the tags exist already")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(--"tag-template" <TEMPLATE> "Template of the tag names, where {name} and {version} are replaced by the package name and new version. Defaults to the stable workspace's [workspace.metadata.workspace-version-tools] tag-template, or \"{name}-v{version}\"")
+                        .requires("tag")
+                        .value_parser(value_parser!(String)),
+                    clap::arg!(-f --format <FORMAT> "Print the bump tree as a graph when dry-running instead, either Graphviz \"dot\" or \"mermaid\". Significant edges, which decided a package's highest bump, are drawn bold, and major bumps red. Or print Markdown \"release-notes\" listing every bump, grouped by breaking and compatible changes")
+                        .value_parser(["dot", "mermaid", "release-notes"]),
+                    clap::arg!(-o --"plan-out" <PATH> "Write the computed bumps to a plan file for `apply` instead of applying them")
+                        .value_parser(value_parser!(PathBuf)),
+                    clap::arg!(-i --"prerelease-identifier" <IDENTIFIER> "Identifier for new prerelease versions, e.g. \"beta\". Defaults to the prerelease workspace's [workspace.metadata.workspace-version-tools] prerelease-identifier, or \"alpha\". Packages already on a prerelease keep their identifier.")
+                        .value_parser(value_parser!(Prerelease))
                 ])
                 .subcommand(
                     clap::command!("stable")
@@ -63,6 +102,11 @@ async fn run() -> Result<(), String> {
                         .args(&[
                             clap::arg!(-p --"prerelease-branch" <PRERELEASE_BRANCH> "Also update a prerelease branch to keep the version distance the same after this change"),
                         ])
+                        .subcommand(
+                            clap::command!("explain")
+                                .about("Explain why a package is bumped, printing every path to it from the requested bumps instead of applying them")
+                                .arg(clap::arg!(<PACKAGE> "Package to explain the bump of"))
+                        )
                 )
                 .subcommand(
                     clap::command!("prerelease")
@@ -70,7 +114,67 @@ async fn run() -> Result<(), String> {
                         .args(&[
                             clap::arg!(-s --"stable-branch" <STABLE_BRANCH> "Stable branch to cap the bump at"),
                         ])
+                        .subcommand(
+                            clap::command!("explain")
+                                .about("Explain why a package is bumped, printing every path to it from the requested bumps instead of applying them")
+                                .arg(clap::arg!(<PACKAGE> "Package to explain the bump of"))
+                        )
                 )
+        )
+        .subcommand(
+            clap::command!("changed")
+                .about("List the packages with files changed between a git ref and the working tree")
+                .args(&[
+                    clap::arg!(-s --since <REF> "Tag, branch or commit to find changes since")
+                        .required(true)
+                        .value_parser(value_parser!(String)),
+                    clap::arg!(-b --"emit-bump-instructions" <BUMP_TYPE> "Also print a --bump-instruction of this bump type for every changed package, ready to pass to `bump`")
+                        .value_parser(["major", "minor", "patch", "prerelease"]),
+                ])
+        )
+        .subcommand(
+            clap::command!("publish")
+                .about("Publish every package in dependency order to the registries its publish field allows, skipping packages with publish = false or whose version is already published")
+                .args(&[
+                    clap::arg!(-d --"dry-run" [BOOL] "Whether to run cargo publish --dry-run instead")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(--index <URL> "Registry index to check for published versions instead of each registry's own, either sparse+http(s)://, or a local directory laid out like an index")
+                        .value_parser(value_parser!(String)),
+                    clap::arg!(--retries <RETRIES> "Times to retry a failed cargo publish")
+                        .default_value("3")
+                        .value_parser(value_parser!(u32)),
+                    clap::arg!(--"retry-delay" <SECONDS> "Seconds to wait before retrying, and between checks of whether a published version is in the index")
+                        .default_value("10")
+                        .value_parser(value_parser!(u64)),
+                    clap::arg!(--"wait-timeout" <SECONDS> "Seconds to wait for a published version to appear in the index before publishing its dependents")
+                        .default_value("300")
+                        .value_parser(value_parser!(u64)),
+                ])
+        )
+        .subcommand(
+            clap::command!("apply")
+                .about("Apply a plan written by `bump --plan-out`, if the workspace versions still match those it was planned from")
+                .args(&[
+                    clap::arg!(<PLAN> "Path of the plan file")
+                        .value_parser(value_parser!(PathBuf)),
+                    clap::arg!(-d --"dry-run" [BOOL] "Whether to dry-run the change, printing a diff of every file which would change instead of applying it")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(-t --worktree [BOOL] "Whether to apply the bumps in temporary git worktrees, leaving any uncommitted changes in the current checkout untouched. The checkout only moves if its branch is bumped")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(-g --tag [BOOL] "Whether to create an annotated tag for each package bumped on the stable branch, refusing to apply the bumps if any of the tags exist already")
+                        .default_value("false")
+                        .default_missing_value("true")
+                        .value_parser(value_parser!(bool)),
+                    clap::arg!(--"tag-template" <TEMPLATE> "Template of the tag names, where {name} and {version} are replaced by the package name and new version. Defaults to the stable workspace's [workspace.metadata.workspace-version-tools] tag-template, or \"{name}-v{version}\"")
+                        .requires("tag")
+                        .value_parser(value_parser!(String)),
+                ])
         );
 
     let matches = cmd.get_matches();
@@ -82,53 +186,157 @@ async fn run() -> Result<(), String> {
     let remote_name = matches
         .get_one::<String>("git-remote")
         .expect("--git-remote is required");
-    let mut workspace = Workspace::new(workspace_path.clone(), None, remote_name)?;
+    let user_agent = matches
+        .get_one::<String>("user-agent")
+        .expect("--user-agent has a default");
+    let output = OutputFormat::from_str(
+        matches
+            .get_one::<String>("output")
+            .expect("--output has a default"),
+    )?;
+    // Worktrees only need the committed workspaces, so don't mind uncommitted changes
+    let worktree = matches
+        .subcommand_matches("bump")
+        .or_else(|| matches.subcommand_matches("apply"))
+        .and_then(|m| m.get_one::<bool>("worktree"))
+        .copied()
+        .unwrap_or(false);
+    let load_workspace = match (worktree, matches.subcommand_name()) {
+        (true, _) => Workspace::new_from_git,
+        // Reports uncommitted changes, so mustn't refuse them
+        (false, Some("changed")) => Workspace::new_read_only,
+        (false, _) => Workspace::new,
+    };
+    let mut workspace = load_workspace(workspace_path.clone(), None, remote_name)?;
 
     match matches.subcommand() {
         Some(("sync", _)) => {
-            commands::sync::exec(&mut workspace).await;
+            let mut registries = Registries::new(&workspace_path, user_agent, None);
+            commands::sync::exec(&mut workspace, &mut registries, output).await;
             Ok(())
         }
         Some(("make-at-least-stable", _)) => {
-            commands::make_at_least_stable::exec(&mut workspace).await;
+            commands::make_at_least_stable::exec(&mut workspace, output).await;
             Ok(())
         }
         Some(("bump", matches)) => {
-            let bump_instructions = matches
-                .get_many::<String>("bump-instruction")
-                .expect("--bump-instruction is required")
+            let from_changesets = *matches
+                .get_one::<bool>("from-changesets")
+                .expect("--from-changesets has a default");
+            let changesets = match from_changesets {
+                true => {
+                    let changesets = read_changesets(&workspace)?;
+                    log::info!(
+                        "📚 Read {} changesets from branch {}",
+                        changesets.len(),
+                        workspace.branch_name
+                    );
+                    changesets
+                }
+                false => vec![],
+            };
+            let bump_instructions = match matches.get_one::<String>("since") {
+                Some(since) => {
+                    let inferred = infer_bump_instructions(&workspace, since)?;
+                    log::info!(
+                        "🔮 Inferred bump instructions from the commits since {}: {}",
+                        since,
+                        inferred.instructions.join(", ")
+                    );
+                    if !inferred.unclassified.is_empty() {
+                        log::warn!(
+                            "⚠️ {} commits touching packages couldn't be classified and were ignored",
+                            inferred.unclassified.len()
+                        );
+                    }
+                    inferred.instructions
+                }
+                None if from_changesets => merge_instructions(&changesets)?,
+                None => matches
+                    .get_many::<String>("bump-instruction")
+                    .expect("--bump-instruction is required without --since or --from-changesets")
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            };
+            let notes = matches
+                .get_many::<String>("note")
+                .unwrap_or_default()
+                .map(|s| s.as_str())
                 .collect::<Vec<_>>();
             let dry_run = matches
                 .get_one::<bool>("dry-run")
                 .expect("--dry-run is required");
+            let prerelease_identifier = matches.get_one::<Prerelease>("prerelease-identifier");
+            let tag = *matches.get_one::<bool>("tag").expect("--tag has a default");
+            let tag_template = matches.get_one::<String>("tag-template");
+            let plan_out = matches.get_one::<PathBuf>("plan-out");
+            let output = match matches.get_one::<String>("format").map(|f| f.as_str()) {
+                None => output,
+                Some("dot" | "mermaid") if !dry_run => {
+                    return Err("Graph formats can only be used with --dry-run".to_owned())
+                }
+                Some(_) if output == OutputFormat::Json => {
+                    return Err("--format can't be combined with --output json".to_owned())
+                }
+                Some("dot") => OutputFormat::Dot,
+                Some("mermaid") => OutputFormat::Mermaid,
+                Some("release-notes") => OutputFormat::ReleaseNotes,
+                Some(_) => unreachable!("clap should ensure we don't get here"),
+            };
             match matches.subcommand() {
                 Some(("stable", matches)) => {
                     let prerelease_workspace = matches
                         .get_one::<String>("prerelease-branch")
-                        .map(|b| Workspace::new(workspace_path, Some(b.as_str()), remote_name));
+                        .map(|b| load_workspace(workspace_path, Some(b.as_str()), remote_name));
 
-                    let prerelease_workspace = match prerelease_workspace {
+                    let mut prerelease_workspace = match prerelease_workspace {
                         Some(Ok(prerelease_workspace)) => Some(prerelease_workspace),
                         Some(Err(e)) => return Err(e),
                         None => None,
                     };
+                    if let (Some(w), Some(i)) = (&mut prerelease_workspace, prerelease_identifier) {
+                        w.prerelease_identifier = i.clone();
+                    }
+
+                    let prerelease_workspace = prerelease_workspace
+                        .as_mut()
+                        .expect("Currently must also update prerelease branch");
+                    if let Some(("explain", matches)) = matches.subcommand() {
+                        return commands::bump::exec_explain(
+                            &workspace,
+                            prerelease_workspace,
+                            bump_instructions.iter().map(|s| s.as_str()).collect(),
+                            ReleaseChannel::Stable,
+                            matches
+                                .get_one::<String>("PACKAGE")
+                                .expect("PACKAGE is required"),
+                        );
+                    }
 
                     commands::bump::exec_stable(
                         &mut workspace,
-                        prerelease_workspace
-                            .expect("Currently must also update prerelease branch")
-                            .borrow_mut(),
+                        prerelease_workspace,
                         bump_instructions
                             .iter()
                             .map(|s| s.as_str())
                             .collect::<Vec<_>>(),
+                        &changesets,
+                        &notes,
                         *dry_run,
+                        worktree,
+                        tag,
+                        tag_template.map(|t| t.as_str()),
+                        plan_out.map(|p| p.as_path()),
+                        output,
                     )
                 }
                 Some(("prerelease", matches)) => {
+                    if let Some(i) = prerelease_identifier {
+                        workspace.prerelease_identifier = i.clone();
+                    }
                     let stable_workspace = matches
                         .get_one::<String>("stable-branch")
-                        .map(|b| Workspace::new(workspace_path, Some(b.as_str()), remote_name));
+                        .map(|b| load_workspace(workspace_path, Some(b.as_str()), remote_name));
 
                     let stable_workspace = match stable_workspace {
                         Some(Ok(w)) => Some(w),
@@ -136,21 +344,103 @@ async fn run() -> Result<(), String> {
                         None => None,
                     };
 
+                    let mut stable_workspace =
+                        stable_workspace.expect("Currently must also update stable branch");
+                    if let Some(("explain", matches)) = matches.subcommand() {
+                        return commands::bump::exec_explain(
+                            &stable_workspace,
+                            &workspace,
+                            bump_instructions.iter().map(|s| s.as_str()).collect(),
+                            ReleaseChannel::Prerelease,
+                            matches
+                                .get_one::<String>("PACKAGE")
+                                .expect("PACKAGE is required"),
+                        );
+                    }
+
                     commands::bump::exec_prerelease(
-                        stable_workspace
-                            .expect("Currently must also update stable branch")
-                            .borrow_mut(),
+                        &mut stable_workspace,
                         &mut workspace,
                         bump_instructions
                             .iter()
                             .map(|s| s.as_str())
                             .collect::<Vec<_>>(),
+                        &changesets,
+                        &notes,
                         *dry_run,
+                        worktree,
+                        tag,
+                        tag_template.map(|t| t.as_str()),
+                        plan_out.map(|p| p.as_path()),
+                        output,
                     )
                 }
                 _ => unreachable!("clap should ensure we don't get here"),
             }
         }
+        Some(("changed", matches)) => {
+            let since = matches
+                .get_one::<String>("since")
+                .expect("--since is required");
+            let bump_type = matches
+                .get_one::<String>("emit-bump-instructions")
+                .map(|b| BumpType::from_str(b))
+                .transpose()?;
+            commands::changed::exec(&workspace, since, bump_type, output)
+        }
+        Some(("publish", matches)) => {
+            let mut registries = Registries::new(
+                &workspace_path,
+                user_agent,
+                matches.get_one::<String>("index").map(|i| i.as_str()),
+            );
+            let seconds = |arg: &str| {
+                Duration::from_secs(*matches.get_one::<u64>(arg).expect("has a default"))
+            };
+            let options = commands::publish::PublishOptions {
+                dry_run: *matches
+                    .get_one::<bool>("dry-run")
+                    .expect("--dry-run has a default"),
+                retries: *matches
+                    .get_one::<u32>("retries")
+                    .expect("--retries has a default"),
+                delay: seconds("retry-delay"),
+                wait_timeout: seconds("wait-timeout"),
+            };
+            commands::publish::exec(&workspace, &mut registries, &options, output).await
+        }
+        Some(("apply", matches)) => {
+            let plan = BumpPlan::read(
+                matches
+                    .get_one::<PathBuf>("PLAN")
+                    .expect("PLAN is required"),
+            )?;
+            let dry_run = matches
+                .get_one::<bool>("dry-run")
+                .expect("--dry-run is required");
+            let mut stable_workspace = load_workspace(
+                workspace_path.clone(),
+                Some(plan.stable_branch.as_str()),
+                remote_name,
+            )?;
+            let mut prerelease_workspace = load_workspace(
+                workspace_path,
+                Some(plan.prerelease_branch.as_str()),
+                remote_name,
+            )?;
+            commands::apply::exec(
+                &mut stable_workspace,
+                &mut prerelease_workspace,
+                &plan,
+                *dry_run,
+                worktree,
+                *matches.get_one::<bool>("tag").expect("--tag has a default"),
+                matches
+                    .get_one::<String>("tag-template")
+                    .map(|t| t.as_str()),
+                output,
+            )
+        }
         _ => unreachable!("clap should ensure we don't get here"),
     }
 }